@@ -0,0 +1,27 @@
+//! Decodes arbitrary bytes as a CEM file, and writes anything that decodes to every output format.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cemconv;
+
+use cemconv::convert::{self, ColladaFormat, Format, ObjFormat};
+use std::io;
+
+fuzz_target!(|data: &[u8]| {
+	let input = Format::cem();
+
+	let outputs = [
+		Format::cem(),
+		Format::Obj(ObjFormat { debug_bounds: true, ..ObjFormat::default() }),
+		Format::Collada(ColladaFormat { debug_bounds: true, ..ColladaFormat::default() }),
+		Format::Json,
+		Format::Ron
+	];
+
+	for output in &outputs {
+		// Errors are expected, only panics, aborts, and timeouts count as findings.
+		let _ = convert::convert(data, io::sink(), None, &input, output);
+	}
+});
@@ -0,0 +1,17 @@
+//! Imports arbitrary text as a COLLADA document and writes the result as a CEM file.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cemconv;
+
+use cemconv::convert::{self, Format};
+use std::io;
+
+fuzz_target!(|data: &[u8]| {
+	let input = Format::collada();
+	let output = Format::cem();
+
+	let _ = convert::convert(data, io::sink(), None, &input, &output);
+});
@@ -0,0 +1,45 @@
+//! Decodes arbitrary bytes as a CEM file and runs anything valid through the processing passes of CEM output. The
+//! first byte chooses the passes, the rest is the file.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cemconv;
+
+use cemconv::convert::{self, CemFormat, Format};
+use cemconv::normals::NormalMode;
+use cemconv::transform::{self, Axis, Rotation};
+use std::io;
+
+fuzz_target!(|data: &[u8]| {
+	let (passes, file) = match data.split_first() {
+		Some((&passes, file)) => (passes, file),
+		None => return
+	};
+
+	// A mirrored, rotated, non-uniform transform also exercises the winding flip and the normal matrix.
+	let matrix = transform::matrix(
+		transform::parse_scale("2,1,0.5").unwrap(),
+		&[Axis::X],
+		&["z:30".parse::<Rotation>().unwrap()],
+		transform::parse_vector("1,-2,3").unwrap()
+	);
+
+	let output = Format::Cem(CemFormat {
+		transform: if passes & 1 != 0 { Some(matrix) } else { None },
+		weld: if passes & 2 != 0 { Some(0.01) } else { None },
+		optimize: passes & 4 != 0,
+		recompute_bounds: passes & 8 != 0,
+		normals: match (passes >> 4) & 3 {
+			0 => None,
+			1 => Some(NormalMode::Smooth),
+			2 => Some(NormalMode::Flat),
+			_ => Some(NormalMode::Angle(45.0))
+		},
+		..CemFormat::default()
+	});
+
+	// Files that fail validation are rejected before the passes run. Only panics, aborts, and timeouts count.
+	let _ = convert::convert(file, io::sink(), None, &Format::cem(), &output);
+});
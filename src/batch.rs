@@ -0,0 +1,165 @@
+use glob::{self, MatchOptions, Pattern};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+
+/// Whether the input names a directory or glob pattern, and so should be converted as a batch.
+pub fn is_batch(input: &str) -> bool {
+	Path::new(input).is_dir() || input.contains(['*', '?', '['])
+}
+
+/// A single file of a batch.
+pub struct Job {
+	pub input: PathBuf,
+	pub output: PathBuf
+}
+
+/// Finds the files matched by a directory or glob pattern, in sorted order. Directories are searched recursively for
+/// files with one of the given extensions.
+pub fn inputs(input: &str, extensions: &[&str]) -> io::Result<Vec<PathBuf>> {
+	let (patterns, options) = if Path::new(input).is_dir() {
+		let escaped = Pattern::escape(input);
+		let patterns = extensions.iter().map(|extension| format!("{}/**/*.{}", escaped, extension)).collect::<Vec<_>>();
+
+		// Game data folders mix upper and lower case extensions.
+		(patterns, MatchOptions { case_sensitive: false, ..MatchOptions::new() })
+	} else {
+		(vec![input.to_owned()], MatchOptions::new())
+	};
+
+	let mut files = Vec::new();
+
+	for pattern in patterns {
+		let paths = glob::glob_with(&pattern, options).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid pattern {:?}: {}", pattern, e)))?;
+
+		for path in paths {
+			let path = path.map_err(|e| io::Error::new(e.error().kind(), e.to_string()))?;
+
+			if path.is_file() {
+				files.push(path);
+			}
+		}
+	}
+
+	files.sort();
+	files.dedup();
+
+	Ok(files)
+}
+
+/// Finds the files matched by a directory or glob pattern as with `inputs`. The output path of each file mirrors its
+/// location relative to the directory, or to the part of the pattern before the first wildcard, with the file name
+/// given by the template.
+pub fn collect(input: &str, extensions: &[&str], output: &Path, template: &str, output_extension: &str) -> io::Result<Vec<Job>> {
+	let base = if Path::new(input).is_dir() { PathBuf::from(input) } else { literal_prefix(input) };
+
+	let jobs = inputs(input, extensions)?.into_iter().map(|path| {
+		let relative = path.strip_prefix(&base).unwrap_or(&path).to_owned();
+
+		let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+		let name = template.replace("{stem}", &stem).replace("{ext}", output_extension);
+
+		let mut destination = output.to_owned();
+
+		if let Some(parent) = relative.parent() {
+			destination.push(parent);
+		}
+
+		destination.push(name);
+
+		Job { input: path, output: destination }
+	}).collect();
+
+	Ok(jobs)
+}
+
+/// Checks that no two jobs write to the same output, such as `a.cem` and `a.CEM` next to each other, or any two inputs
+/// with a template that leaves out `{stem}`, so that nothing is converted only to be overwritten.
+pub fn check_outputs(jobs: &[Job]) -> io::Result<()> {
+	let mut destinations = HashMap::new();
+
+	for job in jobs {
+		if let Some(other) = destinations.insert(&job.output, &job.input) {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+				"{} and {} would both be written to {}, use a --name template that tells them apart",
+				other.display(), job.input.display(), job.output.display()
+			)));
+		}
+	}
+
+	Ok(())
+}
+
+/// The leading directories of a glob pattern that don't contain any wildcards.
+fn literal_prefix(pattern: &str) -> PathBuf {
+	let mut prefix = PathBuf::new();
+	let components = Path::new(pattern).components().collect::<Vec<_>>();
+
+	// The last component is always the file name part of the pattern.
+	for component in &components[..components.len().saturating_sub(1)] {
+		if let Component::Normal(part) = *component {
+			if part.to_string_lossy().contains(['*', '?', '[']) {
+				break;
+			}
+		}
+
+		prefix.push(component);
+	}
+
+	prefix
+}
+
+/// Converts every job in parallel, continuing past failures, and prints a summary. Returns the number of failed jobs.
+///
+/// Each output is written to a temporary file next to it, which only replaces the output once the conversion has
+/// succeeded. A failed job leaves any existing output alone, which also makes converting a directory into itself safe.
+pub fn run<F>(jobs: &[Job], convert: F) -> usize where F: Fn(&Path, &Path, &mut io::BufWriter<File>) -> io::Result<()> + Sync {
+	let failures = jobs.par_iter().filter_map(|job| {
+		let temporary = temporary_path(&job.output);
+
+		let result = match job.output.parent() {
+			Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+			_ => Ok(())
+		}.and_then(|_| {
+			let mut o = io::BufWriter::new(File::create(&temporary)?);
+
+			// A panic in one of the converters should only fail its own file.
+			panic::catch_unwind(AssertUnwindSafe(|| convert(&job.input, &job.output, &mut o)))
+				.unwrap_or_else(|_| Err(io::Error::other("the converter panicked")))?;
+
+			o.flush()?;
+			drop(o);
+
+			fs::rename(&temporary, &job.output)
+		});
+
+		match result {
+			Ok(()) => None,
+			Err(e) => {
+				// Don't leave partially written files behind.
+				let _ = fs::remove_file(&temporary);
+
+				eprintln!("error: failed to convert {}: {}", job.input.display(), e);
+				Some((&job.input, e))
+			}
+		}
+	}).collect::<Vec<_>>();
+
+	eprintln!("converted {} of {} files, {} failed", jobs.len() - failures.len(), jobs.len(), failures.len());
+
+	for (input, e) in &failures {
+		eprintln!("  {}: {}", input.display(), e);
+	}
+
+	failures.len()
+}
+
+/// A hidden file in the same directory as the output, so that renaming it over the output never crosses file systems.
+fn temporary_path(output: &Path) -> PathBuf {
+	let name = output.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+	output.with_file_name(format!(".{}.cemconv-tmp", name))
+}
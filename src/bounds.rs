@@ -0,0 +1,142 @@
+use cem::{v2, V2, Scene};
+use cem::collider::{Aabb, CenterBuilder, Collider};
+use cgmath::{Point3, Vector3, Rad, Angle};
+use std::f32::consts::PI;
+use std::mem;
+
+/// Rebuilds the center of the scene and its children from the first frame, and the bounding box and radius of every
+/// frame from its vertices, the same way the importers compute them. If verbose, the old and new values are printed.
+pub fn recompute(scene: &mut Scene<V2>, verbose: bool) {
+	recompute_model(&mut scene.model, &scene.name, verbose);
+
+	for child in &mut scene.children {
+		recompute(child, verbose);
+	}
+}
+
+/// Rebuilds the center and bounds of a single model as with `recompute`, leaving any child scenes alone. The name is
+/// only used when printing.
+pub fn recompute_model(model: &mut V2, name: &str, verbose: bool) {
+	// A model without any vertices keeps its center, as there is nothing to center it on.
+	let center = match model.frames.first() {
+		Some(frame) if !frame.vertices.is_empty() => {
+			let mut center_builder = CenterBuilder::begin();
+
+			for vertex in &frame.vertices {
+				center_builder.update(vertex.position);
+			}
+
+			center_builder.build()
+		},
+		_ => model.center
+	};
+
+	if verbose {
+		eprintln!("scene {:?}: center {:?} -> {:?}", name, array(model.center), array(center));
+	}
+
+	model.center = center;
+
+	for (index, frame) in model.frames.iter_mut().enumerate() {
+		let vertices = mem::take(&mut frame.vertices);
+		let tag_points = mem::take(&mut frame.tag_points);
+		let old = frame.collider;
+
+		let rebuilt = v2::Frame::from_vertices(vertices, tag_points, center);
+
+		frame.vertices = rebuilt.vertices;
+		frame.tag_points = rebuilt.tag_points;
+		frame.collider = rebuilt.collider;
+
+		if verbose {
+			eprintln!("scene {:?}, frame {}: {} -> {}", name, index, describe(&old), describe(&frame.collider));
+		}
+	}
+}
+
+fn describe(collider: &Collider) -> String {
+	format!(
+		"bounds {:?} to {:?}, radius {}",
+		array(collider.aabb.lower),
+		array(collider.aabb.upper),
+		collider.radius
+	)
+}
+
+fn array(point: Point3<f32>) -> [f32; 3] {
+	point.into()
+}
+
+/// Number of line segments used to draw each circle of the bounding sphere.
+const CIRCLE_SEGMENTS: u32 = 32;
+
+/// Lines outlining bounding volumes, so that exporters can show how they line up with the mesh.
+pub struct Wireframe {
+	pub positions: Vec<Point3<f32>>,
+	/// Pairs of indices into the positions.
+	pub lines: Vec<[u32; 2]>
+}
+
+impl Wireframe {
+	/// The bounding box of the frame, and a circle in each axis plane showing the radius around the model center.
+	pub fn frame(frame: &v2::Frame, center: Point3<f32>) -> Self {
+		let mut wireframe = Wireframe { positions: Vec::new(), lines: Vec::new() };
+		let Aabb { lower, upper } = frame.collider.aabb;
+
+		// Corners are numbered by which coordinates come from the upper corner, so edges connect numbers that differ
+		// in a single bit.
+		for corner in 0..8 {
+			wireframe.positions.push(Point3::new(
+				if corner & 1 == 0 { lower.x } else { upper.x },
+				if corner & 2 == 0 { lower.y } else { upper.y },
+				if corner & 4 == 0 { lower.z } else { upper.z }
+			));
+		}
+
+		for corner in 0..8 {
+			for &bit in &[1, 2, 4] {
+				if corner & bit == 0 {
+					wireframe.lines.push([corner, corner | bit]);
+				}
+			}
+		}
+
+		let radius = frame.collider.radius;
+
+		for &(u, v) in &[(Vector3::unit_x(), Vector3::unit_y()), (Vector3::unit_y(), Vector3::unit_z()), (Vector3::unit_z(), Vector3::unit_x())] {
+			let first = wireframe.positions.len() as u32;
+
+			for segment in 0..CIRCLE_SEGMENTS {
+				let angle = Rad(segment as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI);
+
+				wireframe.positions.push(center + (u * angle.cos() + v * angle.sin()) * radius);
+				wireframe.lines.push([first + segment, first + (segment + 1) % CIRCLE_SEGMENTS]);
+			}
+		}
+
+		wireframe
+	}
+
+	/// A cross marking the center, with arms of the given length along each axis.
+	pub fn center(center: Point3<f32>, size: f32) -> Self {
+		let mut wireframe = Wireframe { positions: Vec::new(), lines: Vec::new() };
+
+		for &axis in &[Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+			let first = wireframe.positions.len() as u32;
+
+			wireframe.positions.push(center - axis * size);
+			wireframe.positions.push(center + axis * size);
+			wireframe.lines.push([first, first + 1]);
+		}
+
+		wireframe
+	}
+
+	/// Size of the center marker of a model: a tenth of the radius of the first frame, or 1 if that is empty.
+	pub fn center_size(model: &V2) -> f32 {
+		match model.frames.first().map(|frame| frame.collider.radius) {
+			Some(radius) if radius.is_normal() && radius > 0.0 => radius / 10.0,
+			_ => 1.0
+		}
+	}
+}
@@ -1,28 +1,17 @@
+use bounds::Wireframe;
 use cem::{v2, V2, Scene};
-use cgmath::{Point3, Matrix4, Deg, InnerSpace};
-use std::fmt::{self, Write};
-use std::str::FromStr;
-
-// TODO: Date and Time modified
-pub const HEADER: &'static str = r#"<?xml version="1.0" encoding="utf-8"?>
-<COLLADA xmlns="http://www.collada.org/2005/11/COLLADASchema" version="1.4.1">
-  <asset>
-    <contributor>
-      <author>cemconv user</author>
-      <authoring_tool>cemconv 0.2.0 collada exporter</authoring_tool>
-    </contributor>
-    <created>2018-01-01T00:00:00</created>
-    <modified>2018-01-01T00:00:00</modified>
-    <unit name="meter" meter="1"/>
-    <up_axis>Y_UP</up_axis>
-  </asset>
-  <library_cameras/>
-  <library_images/>
-  <library_geometries>
-"#;
-
-const FORMAT_POS: &'static str = r##"<param name="X" type="float"/><param name="Y" type="float"/><param name="Z" type="float"/>"##;
-const FORMAT_TEX: &'static str = r##"<param name="S" type="float"/><param name="T" type="float"/>"##;
+use cgmath::{Point3, Matrix4, InnerSpace};
+use collada_import::UpAxis;
+use tags::Light;
+use chrono::{SecondsFormat, Utc};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use xml_writer::{Ids, XmlWriter};
+
+const NAMESPACE: &str = "http://www.collada.org/2005/11/COLLADASchema";
+
+const PARAMS_POS: &[&str] = &["X", "Y", "Z"];
+const PARAMS_TEX: &[&str] = &["S", "T"];
 
 struct Geometry<'n> {
 	// Name
@@ -37,103 +26,105 @@ struct Geometry<'n> {
 	polygons: Vec<u32>
 }
 
-impl<'n> fmt::Display for Geometry<'n> {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		writeln!(f, r#"    <geometry id="{0}-mesh" name="{0}">"#, self.name)?;
-		writeln!(f, r#"      <mesh>"#)?;
-
-		let vertex_count = self.mesh_positions.len() / 3;
-
-		{
-			let mut write_source = |source: &str, array: &[f32], stride: usize, format: &str| -> fmt::Result {
-				writeln!(f, r#"        <source id="{}-{}">"#, self.name, source)?;
-
-				writeln!(f, r#"          <float_array id="{}-{}-array" count="{}">"#, self.name, source, array.len())?;
-				for position in array {
-					write!(f, "{:.8} ", position)?;
-				}
-				writeln!(f, r#"          </float_array>"#)?;
-				writeln!(f, r##"<technique_common><accessor source="#{}-{}-array" count="{}" stride="{}">"##, self.name, source, vertex_count, stride)?;
-				writeln!(f, "{}", format)?;
-				writeln!(f, r##"</accessor></technique_common>"##)?;
-				writeln!(f, r#"        </source>"#)
-			};
-
-			write_source("mesh-positions", &self.mesh_positions, 3, FORMAT_POS)?;
-			write_source("mesh-normals", &self.mesh_normals, 3, FORMAT_POS)?;
-			write_source("mesh-map", &self.mesh_map, 2, FORMAT_TEX)?;
-		}
+impl<'n> Geometry<'n> {
+	/// Writes the geometry element, returning its id.
+	fn write<W>(&self, xml: &mut XmlWriter<W>, ids: &mut Ids) -> io::Result<String> where W: Write {
+		let id = ids.unique(&format!("{}-mesh", self.name));
 
-		writeln!(f, r##"        <vertices id="{0}-mesh-vertices"><input semantic="POSITION" source="#{0}-mesh-positions"/></vertices>"##, self.name)?;
+		xml.start("geometry", &[("id", &id), ("name", self.name)])?;
+		xml.start("mesh", &[])?;
 
-		writeln!(f, r#"        <triangles count="{}">"#, self.polygons.len() / 3)?;
-		writeln!(f, r##"          <input semantic="VERTEX" source="#{}-mesh-vertices" offset="0"/>"##, self.name)?;
-		writeln!(f, r##"          <input semantic="NORMAL" source="#{}-mesh-normals" offset="1"/>"##, self.name)?;
-		writeln!(f, r##"          <input semantic="TEXCOORD" source="#{}-mesh-map" offset="2" set="0"/>"##, self.name)?;
+		let positions = write_source(xml, ids, &id, "positions", &self.mesh_positions, PARAMS_POS)?;
+		let normals = write_source(xml, ids, &id, "normals", &self.mesh_normals, PARAMS_POS)?;
+		let map = write_source(xml, ids, &id, "map", &self.mesh_map, PARAMS_TEX)?;
 
-		write!(f, r#"          <p>"#)?;
-		for index in &self.polygons {
-			write!(f, "{0} {0} {0} ", index)?;
-		}
-		writeln!(f, r#"          </p>"#)?;
-		writeln!(f, r#"        </triangles>"#)?;
-		writeln!(f, r#"      </mesh>"#)?;
-		write!(f, r#"    </geometry>"#)?;
+		let vertices = ids.unique(&format!("{}-vertices", id));
+
+		xml.start("vertices", &[("id", &vertices)])?;
+		xml.empty("input", &[("semantic", "POSITION"), ("source", &format!("#{}", positions))])?;
+		xml.end()?;
+
+		xml.start("triangles", &[("count", &(self.polygons.len() / 3).to_string())])?;
+		xml.empty("input", &[("semantic", "VERTEX"), ("source", &format!("#{}", vertices)), ("offset", "0")])?;
+		xml.empty("input", &[("semantic", "NORMAL"), ("source", &format!("#{}", normals)), ("offset", "1")])?;
+		xml.empty("input", &[("semantic", "TEXCOORD"), ("source", &format!("#{}", map)), ("offset", "2"), ("set", "0")])?;
+
+		xml.start("p", &[])?;
+		xml.list(self.polygons.iter().map(|index| format!("{0} {0} {0}", index)))?;
+		xml.end()?;
 
+		xml.end()?;
+		xml.end()?;
+		xml.end()?;
 
-		Ok(())
+		Ok(id)
 	}
 }
 
-struct Light {
-	color: (f32, f32, f32),
-	unk: (u32, u32, u32)
+/// Writes a source of floats with the given parameters per element, returning its id.
+fn write_source<W>(xml: &mut XmlWriter<W>, ids: &mut Ids, id: &str, source: &str, array: &[f32], params: &[&str]) -> io::Result<String> where W: Write {
+	let source_id = ids.unique(&format!("{}-{}", id, source));
+	let array_id = ids.unique(&format!("{}-array", source_id));
+
+	xml.start("source", &[("id", &source_id)])?;
+
+	xml.start("float_array", &[("id", &array_id), ("count", &array.len().to_string())])?;
+	xml.list(array.iter().map(|value| format!("{:.8}", value)))?;
+	xml.end()?;
+
+	xml.start("technique_common", &[])?;
+	xml.start("accessor", &[
+		("source", &format!("#{}", array_id)),
+		("count", &(array.len() / params.len()).to_string()),
+		("stride", &params.len().to_string())
+	])?;
+
+	for param in params {
+		xml.empty("param", &[("name", param), ("type", "float")])?;
+	}
+
+	xml.end()?;
+	xml.end()?;
+	xml.end()?;
+
+	Ok(source_id)
 }
 
-impl FromStr for Light {
-	type Err = &'static str;
+/// Writes a geometry made of lines, returning its id.
+fn write_wireframe<W>(xml: &mut XmlWriter<W>, ids: &mut Ids, name: &str, wireframe: &Wireframe, transform: Matrix4<f32>) -> io::Result<String> where W: Write {
+	let id = ids.unique(&format!("{}-lines", name));
 
-	fn from_str(definition: &str) -> Result<Self, Self::Err> {
-		fn split(definition: &str) -> Option<(&str, &str, &str, &str, &str, &str)> {
-			let mut split = definition.split('_');
+	let positions = wireframe.positions.iter()
+		.map(|&position| Point3::from_homogeneous(transform * position.to_homogeneous()))
+		.flat_map(|position| vec![position.x, position.y, position.z])
+		.collect::<Vec<f32>>();
 
-			split.next()?; // "light"
+	xml.start("geometry", &[("id", &id), ("name", name)])?;
+	xml.start("mesh", &[])?;
 
-			let r = split.next()?;
-			let g = split.next()?;
-			let b = split.next()?;
+	let positions = write_source(xml, ids, &id, "positions", &positions, PARAMS_POS)?;
+	let vertices = ids.unique(&format!("{}-vertices", id));
 
-			let i = split.next()?;
-			let j = split.next()?;
-			let k = split.next()?;
+	xml.start("vertices", &[("id", &vertices)])?;
+	xml.empty("input", &[("semantic", "POSITION"), ("source", &format!("#{}", positions))])?;
+	xml.end()?;
 
-			Some((r, g, b, i, j, k))
-		};
+	xml.start("lines", &[("count", &wireframe.lines.len().to_string())])?;
+	xml.empty("input", &[("semantic", "VERTEX"), ("source", &format!("#{}", vertices)), ("offset", "0")])?;
 
-		let (r, g, b, i, j, k) = split(definition).ok_or("Invalid light definition")?;
-
-		let (r, g, b, i, j, k) = (
-			r.parse::<u32>().map_err(|_| "failed to parse number")?,
-			g.parse::<u32>().map_err(|_| "failed to parse number")?,
-			b.parse::<u32>().map_err(|_| "failed to parse number")?,
-			i.parse::<u32>().map_err(|_| "failed to parse number")?,
-			j.parse::<u32>().map_err(|_| "failed to parse number")?,
-			k.parse::<u32>().map_err(|_| "failed to parse number")?
-		);
-
-		Ok(Light {
-			color: (
-				(r as f32) / 255.0,
-				(g as f32) / 255.0,
-				(b as f32) / 255.0
-			),
-			unk: (i, j, k)
-		})
-	}
-}
+	xml.start("p", &[])?;
+	xml.list(wireframe.lines.iter().map(|line| format!("{} {}", line[0], line[1])))?;
+	xml.end()?;
+
+	xml.end()?;
+	xml.end()?;
+	xml.end()?;
 
+	Ok(id)
+}
 
-fn write_meshes(name: &str, model: &V2, string: &mut String) {
+/// Writes one geometry per frame, returning the geometry ids in frame order.
+fn write_meshes<W>(xml: &mut XmlWriter<W>, ids: &mut Ids, name: &str, model: &V2, transform: Matrix4<f32>) -> io::Result<Vec<String>> where W: Write {
 	let triangle_data = &model.lod_levels[0];
 	let mut polygons = vec![0; model.lod_levels[0].len() * 3];
 
@@ -150,12 +141,13 @@ fn write_meshes(name: &str, model: &V2, string: &mut String) {
 				vertex_offset + triangle.2
 			);
 
-			polygons[(index as usize)*3 + 0] = indices.0;
-			polygons[(index as usize)*3 + 1] = indices.1;
-			polygons[(index as usize)*3 + 2] = indices.2;
+			let index = index as usize;
+			polygons[index*3..index*3 + 3].copy_from_slice(&[indices.0, indices.1, indices.2]);
 		}
 	}
 
+	let mut geometries = Vec::with_capacity(model.frames.len());
+
 	for (frame_index, frame) in model.frames.iter().enumerate() {
 		let framed_name = format!("{}_frame{}", name, frame_index);
 
@@ -167,133 +159,222 @@ fn write_meshes(name: &str, model: &V2, string: &mut String) {
 			polygons: polygons.clone()
 		};
 
-		let transform = Matrix4::from_angle_x(Deg(-90.0));
-
 		for (index, vertex) in frame.vertices.iter().enumerate() {
 			let normal = (transform * vertex.normal.normalize().extend(0.0)).truncate();
 			let position = Point3::from_homogeneous(transform * vertex.position.to_homogeneous());
 
-			geometry.mesh_positions[index*3 + 0] = position.x;
-			geometry.mesh_positions[index*3 + 1] = position.y;
-			geometry.mesh_positions[index*3 + 2] = position.z;
-
-			geometry.mesh_normals[index*3 + 0] = normal.x;
-			geometry.mesh_normals[index*3 + 1] = normal.y;
-			geometry.mesh_normals[index*3 + 2] = normal.z;
-
-			geometry.mesh_map[index*2 + 0] = vertex.texture.x;
-			geometry.mesh_map[index*2 + 1] = 1.0 - vertex.texture.y;
+			geometry.mesh_positions[index*3..index*3 + 3].copy_from_slice(&[position.x, position.y, position.z]);
+			geometry.mesh_normals[index*3..index*3 + 3].copy_from_slice(&[normal.x, normal.y, normal.z]);
+			geometry.mesh_map[index*2..index*2 + 2].copy_from_slice(&[vertex.texture.x, 1.0 - vertex.texture.y]);
 		}
 
-		writeln!(string, "{}", geometry).unwrap();
+		geometries.push(geometry.write(xml, ids)?);
 	}
-}
 
-pub fn convert(cem: Scene<V2>) -> String {
-	let mut string = String::new();
+	Ok(geometries)
+}
 
-	string.push_str(HEADER);
+fn write_asset<W>(xml: &mut XmlWriter<W>, up_axis: UpAxis) -> io::Result<()> where W: Write {
+	let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
 
-	write_meshes("Scene_Root", &cem.model, &mut string);
+	xml.start("asset", &[])?;
 
-	string.push_str("  </library_geometries>\n");
+	xml.start("contributor", &[])?;
+	xml.element("author", &[], "cemconv user")?;
+	xml.element("authoring_tool", &[], concat!("cemconv ", env!("CARGO_PKG_VERSION"), " collada exporter"))?;
+	xml.end()?;
 
-	string.push_str("  <library_lights>\n");
+	xml.element("created", &[], &now)?;
+	xml.element("modified", &[], &now)?;
+	xml.empty("unit", &[("name", "meter"), ("meter", "1")])?;
+	xml.element("up_axis", &[], up_axis.asset_name())?;
 
-	for name in &cem.model.tag_points {
+	xml.end()
+}
 
-		writeln!(string, "    <light id=\"{}-light\"><technique_common>\n", name).unwrap();
+/// Lists the texture of every material as an image, so that the document refers to the same files as the model.
+fn write_images<W>(xml: &mut XmlWriter<W>, ids: &mut Ids, model: &V2) -> io::Result<()> where W: Write {
+	let mut written = HashSet::new();
 
-		if name.starts_with("light_") {
-			match name.parse::<Light>() {
-				Ok(light) => {
+	xml.start("library_images", &[])?;
 
-					writeln!(string, "    <point><color>{} {} {}</color><linear_attenuation>0.3</linear_attenuation></point>\n", light.color.0, light.color.1, light.color.2).unwrap();
-					string.push_str("    </technique_common></light>\n");
+	for material in &model.materials {
+		let texture_name = &material.texture_name;
 
-					continue;
-				}
-				Err(message) => eprintln!("Failed to parse light \"{}\": {}", name, message)
-			}
+		if texture_name.is_empty() || !written.insert(texture_name) {
+			continue;
 		}
 
-		string.push_str("    <point><color>1.0 1.0 1.0</color><linear_attenuation>0.3</linear_attenuation></point>\n");
-		string.push_str("    </technique_common></light>\n");
+		let id = ids.unique(&format!("{}-image", texture_name));
+
+		xml.start("image", &[("id", &id), ("name", texture_name)])?;
+		xml.element("init_from", &[], &texture_name.replace('\\', "/").replace(' ', "%20"))?;
+		xml.end()?;
 	}
 
-	string.push_str("  </library_lights>\n");
+	xml.end()
+}
 
-	string.push_str("  <library_controllers>\n");
+fn write_light<W>(xml: &mut XmlWriter<W>, id: &str, name: &str) -> io::Result<()> where W: Write {
+	let mut color = [1.0, 1.0, 1.0];
+
+	if name.starts_with("light_") {
+		match name.parse::<Light>() {
+			Ok(light) => color = light.rgb(),
+			Err(message) => eprintln!("Failed to parse light \"{}\": {}", name, message)
+		}
+	}
+
+	xml.start("light", &[("id", id), ("name", name)])?;
+	xml.start("technique_common", &[])?;
+	xml.start("point", &[])?;
+	xml.element("color", &[], &format!("{} {} {}", color[0], color[1], color[2]))?;
+	xml.element("linear_attenuation", &[], "0.3")?;
+	xml.end()?;
+	xml.end()?;
+	xml.end()
+}
+
+fn write_morph<W>(xml: &mut XmlWriter<W>, ids: &mut Ids, name: &str, geometries: &[String]) -> io::Result<()> where W: Write {
+	let targets = &geometries[1..];
+	let count = targets.len().to_string();
+
+	let controller = ids.unique(&format!("{}-morph", name));
+	let targets_id = ids.unique(&format!("{}-targets", name));
+	let targets_array = ids.unique(&format!("{}-array", targets_id));
+	let weights_id = ids.unique(&format!("{}-weights", name));
+	let weights_array = ids.unique(&format!("{}-array", weights_id));
+
+	xml.start("controller", &[("id", &controller), ("name", &format!("{}-morph", name))])?;
+	xml.start("morph", &[("source", &format!("#{}", geometries[0])), ("method", "NORMALIZED")])?;
+
+	// Targets Array
+	xml.start("source", &[("id", &targets_id)])?;
+	xml.start("IDREF_array", &[("id", &targets_array), ("count", &count)])?;
+	xml.list(targets)?;
+	xml.end()?;
+	xml.start("technique_common", &[])?;
+	xml.start("accessor", &[("source", &format!("#{}", targets_array)), ("count", &count), ("stride", "1")])?;
+	xml.empty("param", &[("name", "IDREF"), ("type", "IDREF")])?;
+	xml.end()?;
+	xml.end()?;
+	xml.end()?;
+
+	// Weights Array
+	xml.start("source", &[("id", &weights_id)])?;
+	xml.start("float_array", &[("id", &weights_array), ("count", &count)])?;
+	xml.list(targets.iter().map(|_| 0))?;
+	xml.end()?;
+	xml.start("technique_common", &[])?;
+	xml.start("accessor", &[("source", &format!("#{}", weights_array)), ("count", &count), ("stride", "1")])?;
+	xml.empty("param", &[("name", "MORPH_WEIGHT"), ("type", "float")])?;
+	xml.end()?;
+	xml.end()?;
+	xml.end()?;
+
+	xml.start("targets", &[])?;
+	xml.empty("input", &[("semantic", "MORPH_TARGET"), ("source", &format!("#{}", targets_id))])?;
+	xml.empty("input", &[("semantic", "MORPH_WEIGHT"), ("source", &format!("#{}", weights_id))])?;
+	xml.end()?;
+
+	xml.end()?;
+	xml.end()
+}
+
+/// Writes the root model of a scene as a COLLADA document with the given up axis. With `debug_bounds`, the bounding
+/// volume of every frame and the model center are added as separate line geometries.
+pub fn convert<W>(cem: Scene<V2>, out: W, up_axis: UpAxis, debug_bounds: bool) -> io::Result<()> where W: Write {
+	let mut ids = Ids::new();
+	let mut xml = XmlWriter::new(io::BufWriter::new(out))?;
 
 	let name = "Scene_Root"; // TODO
 	let model = &cem.model;
 
-	if cem.model.frames.len() > 1 {
-		writeln!(string, "    <controller id=\"{0}-morph\" name=\"{0}-morph\">", name).unwrap();
-		writeln!(string, "      <morph source=\"#{}-mesh\" method=\"NORMALIZED\">", name).unwrap();
+	xml.start("COLLADA", &[("xmlns", NAMESPACE), ("version", "1.4.1")])?;
 
-		// Targets Array
-		writeln!(string, "        <source id=\"{}-targets\">", name).unwrap();
-		writeln!(string, "          <IDREF_array id=\"{}-targets-array\" count=\"{}\">", name, model.frames.len()-1).unwrap();
+	write_asset(&mut xml, up_axis)?;
 
-		for frame_index in 1..model.frames.len() {
-			writeln!(string, "            {}_frame{}-mesh", name, frame_index).unwrap();
-		}
+	xml.empty("library_cameras", &[])?;
+	write_images(&mut xml, &mut ids, model)?;
 
-		string.push_str("          </IDREF_array>\n");
-		writeln!(string, r##"<technique_common><accessor source="#{}-targets-array" count="{}" stride="1"><param name="IDREF" type="IDREF"/></accessor></technique_common>"##, name, model.frames.len()-1).unwrap();
+	xml.start("library_geometries", &[])?;
+	let geometries = write_meshes(&mut xml, &mut ids, name, model, up_axis.to_document())?;
 
-		string.push_str("        </source>\n");
+	let mut wireframes = Vec::new();
 
-		// Weights Array
-		writeln!(string, "        <source id=\"{}-weights\">", name).unwrap();
-		write!(string, "          <float_array id=\"{}-weights-array\" count=\"{}\">", name, model.frames.len()-1).unwrap();
+	if debug_bounds {
+		for (index, frame) in model.frames.iter().enumerate() {
+			let wireframe_name = format!("bounds_frame{}", index);
+			let id = write_wireframe(&mut xml, &mut ids, &wireframe_name, &Wireframe::frame(frame, model.center), up_axis.to_document())?;
 
-		for _ in 1..model.frames.len() {
-			string.push_str("0 ");
+			wireframes.push((wireframe_name, id));
 		}
 
-		string.push_str("</float_array>\n");
-		writeln!(string, r##"<technique_common><accessor source="#{}-weights-array" count="{}" stride="1"><param name="MORPH_WEIGHT" type="float"/></accessor></technique_common>"##, name, model.frames.len()-1).unwrap();
+		let center = Wireframe::center(model.center, Wireframe::center_size(model));
+		wireframes.push(("center".to_owned(), write_wireframe(&mut xml, &mut ids, "center", &center, up_axis.to_document())?));
+	}
 
-		string.push_str("        </source>\n");
+	xml.end()?;
 
-		string.push_str("        <targets>\n");
-		writeln!(string, "          <input semantic=\"MORPH_TARGET\" source=\"#{}-targets\"/>", name).unwrap();
-		writeln!(string, "          <input semantic=\"MORPH_WEIGHT\" source=\"#{}-weights\"/>", name).unwrap();
-		string.push_str("        </targets>\n");
-		string.push_str("      </morph>\n");
-		string.push_str("    </controller>\n");
+	let lights = model.tag_points.iter().map(|tag_name| ids.unique(&format!("{}-light", tag_name))).collect::<Vec<_>>();
+
+	xml.start("library_lights", &[])?;
+
+	for (id, tag_name) in lights.iter().zip(model.tag_points.iter()) {
+		write_light(&mut xml, id, tag_name)?;
+	}
+
+	xml.end()?;
+
+	xml.start("library_controllers", &[])?;
+
+	if model.frames.len() > 1 {
+		write_morph(&mut xml, &mut ids, name, &geometries)?;
 	}
 
-	string.push_str("  </library_controllers>\n");
+	xml.end()?;
 
-	string.push_str(r##"  <library_visual_scenes><visual_scene id="Scene" name="Scene">"##);
-	string.push('\n');
+	let scene = ids.unique("Scene");
 
-	writeln!(string, r##"<node id="{0}" name="{0}" type="NODE"><matrix sid="transform">1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1</matrix><instance_geometry url="#{0}-mesh"/>"##, name).unwrap();
+	xml.start("library_visual_scenes", &[])?;
+	xml.start("visual_scene", &[("id", &scene), ("name", "Scene")])?;
+
+	let node = ids.unique(name);
+
+	xml.start("node", &[("id", &node), ("name", name), ("type", "NODE")])?;
+	xml.element("matrix", &[("sid", "transform")], "1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1")?;
+	xml.empty("instance_geometry", &[("url", &format!("#{}", geometries[0]))])?;
 
 	{
-		let transform = Matrix4::from_angle_x(Deg(-90.0));
+		let transform = up_axis.to_document();
 
-		for (tag_name, position) in cem.model.tag_points.iter().zip(cem.model.frames[0].tag_points.iter()) {
+		for ((tag_name, light), position) in model.tag_points.iter().zip(lights.iter()).zip(model.frames[0].tag_points.iter()) {
 			let position = Point3::from_homogeneous(transform * position.to_homogeneous());
+			let id = ids.unique(tag_name);
 
-			writeln!(string, "    <node name=\"{}\">\n", tag_name).unwrap();
-			writeln!(string, "    <translate>{} {} {}</translate>", position.x, position.y, position.z).unwrap();
-			writeln!(string, "    <instance_light url=\"#{}-light\" />\n", tag_name).unwrap();
-			string.push_str("</node>");
+			xml.start("node", &[("id", &id), ("name", tag_name)])?;
+			xml.element("translate", &[], &format!("{} {} {}", position.x, position.y, position.z))?;
+			xml.empty("instance_light", &[("url", &format!("#{}", light))])?;
+			xml.end()?;
 		}
 	}
 
-	string.push_str("</node>");
+	xml.end()?;
+
+	for (wireframe_name, geometry) in &wireframes {
+		let id = ids.unique(wireframe_name);
 
-	string.push_str(r##"  </visual_scene></library_visual_scenes>"##);
-	string.push('\n');
+		xml.start("node", &[("id", &id), ("name", wireframe_name), ("type", "NODE")])?;
+		xml.empty("instance_geometry", &[("url", &format!("#{}", geometry))])?;
+		xml.end()?;
+	}
+
+	xml.end()?;
+	xml.end()?;
 
-	string.push_str(r##"  <scene><instance_visual_scene url="#Scene"/></scene>"##);
-	string.push('\n');
-	string.push_str("</COLLADA>");
+	xml.start("scene", &[])?;
+	xml.empty("instance_visual_scene", &[("url", &format!("#{}", scene))])?;
+	xml.end()?;
 
-	string
-}
\ No newline at end of file
+	xml.finish()?.flush()
+}
@@ -1,21 +1,89 @@
 use cem::{v2, V2, collider};
-use cgmath::{Point3, Point2, Vector3, Matrix4, Deg, InnerSpace};
-use collada::{Object, Shape, VTNIndex, TVertex, Vertex as NVertex};
-use collada::document::ColladaDocument;
+use cgmath::{Point3, Point2, Vector3, Matrix4, Deg, InnerSpace, SquareMatrix, Zero};
+use collada_skin::{Skin, Skeleton};
 use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+use triangulate::triangulate;
 use xml::{self, Element};
 
-pub fn convert(document: ColladaDocument) -> V2 {
-	let mut objects = HashMap::new();
+/// Upper limit on the number of frames baked from a skeletal animation, so that a bogus keyframe time fails instead
+/// of baking for hours.
+const MAX_BAKED_FRAMES: usize = 4096;
 
-	for object in document.get_obj_set().expect("No objects in collada document...").objects {
-		objects.insert(object.id.clone(), object);
+/// The axis pointing up in a COLLADA document. CEM models are always Z up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UpAxis {
+	X,
+	Y,
+	Z
+}
+
+impl UpAxis {
+	pub const NAMES: &'static [&'static str] = &["x", "y", "z"];
+
+	/// Reads the `<up_axis>` of the document asset, which defaults to Y up.
+	fn read(ns: Option<&str>, root: &Element) -> Self {
+		match root.get_child("asset", ns).and_then(|asset| asset.get_child("up_axis", ns)).map(|axis| axis.content_str()) {
+			Some(ref axis) if axis.trim() == "X_UP" => UpAxis::X,
+			Some(ref axis) if axis.trim() == "Z_UP" => UpAxis::Z,
+			_ => UpAxis::Y
+		}
+	}
+
+	/// The value of `<up_axis>` for this axis.
+	pub fn asset_name(self) -> &'static str {
+		match self {
+			UpAxis::X => "X_UP",
+			UpAxis::Y => "Y_UP",
+			UpAxis::Z => "Z_UP"
+		}
 	}
 
-	let ns = document.root_element.ns.as_ref().map(String::as_ref);
+	/// Rotation from this convention to the Z up convention of CEM.
+	pub fn to_cem(self) -> Matrix4<f32> {
+		match self {
+			UpAxis::X => Matrix4::from_angle_y(Deg(-90.0)),
+			UpAxis::Y => Matrix4::from_angle_x(Deg(90.0)),
+			UpAxis::Z => Matrix4::identity()
+		}
+	}
+
+	/// Rotation from the Z up convention of CEM to this convention.
+	pub fn to_document(self) -> Matrix4<f32> {
+		match self {
+			UpAxis::X => Matrix4::from_angle_y(Deg(90.0)),
+			UpAxis::Y => Matrix4::from_angle_x(Deg(-90.0)),
+			UpAxis::Z => Matrix4::identity()
+		}
+	}
+}
+
+impl FromStr for UpAxis {
+	type Err = String;
+
+	fn from_str(axis: &str) -> Result<Self, Self::Err> {
+		match &axis.to_ascii_lowercase() as &str {
+			"x" => Ok(UpAxis::X),
+			"y" => Ok(UpAxis::Y),
+			"z" => Ok(UpAxis::Z),
+			_ => Err(format!("expected x, y, or z, got {:?}", axis))
+		}
+	}
+}
+
+/// Converts the root geometry of a COLLADA document. Skeletal animations are baked into vertex frames by sampling
+/// them at the specified frame rate. The up axis of the document is used unless one is specified. If verbose, the size
+/// of the geometry and the number of baked frames are printed.
+pub fn convert(root: Element, frame_rate: f32, up_axis: Option<UpAxis>, verbose: bool) -> io::Result<V2> {
+	let ns = root.ns.as_ref().map(String::as_ref);
+	let transformation = up_axis.unwrap_or_else(|| UpAxis::read(ns, &root)).to_cem();
+
+	let geometries = root.get_child("library_geometries", ns).ok_or_else(|| invalid("Collada document has no geometry library"))?;
+	let controllers = root.get_child("library_controllers", ns);
 
 	// Find what frames are attached to each piece of geometry
-	let morph_links = document.root_element.get_child("library_controllers", ns).map(|controllers| {
+	let morph_links = controllers.map(|controllers| {
 		controllers
 			.get_children("controller", ns)
 			.filter_map(|controller| controller.get_child("morph", ns))
@@ -32,58 +100,96 @@ pub fn convert(document: ColladaDocument) -> V2 {
 				let target = get_input(ns, morph.get_child("targets", ns)?, "MORPH_TARGET")
 					.and_then(|input_element| get_input_source(ns, morph, input_element))?;
 
-
-				// TODO: We don't actually obey the standard here.
-				// We need to be using the accessor to read the array instead of reading the array directly.
-				// Alas, for now this works (at least with Blender).
-
-				let morph_targets = target.get_child("IDREF_array", ns)?
-					.children
-					.iter()
-					.filter_map(|child| if let &xml::Xml::CharacterNode(ref contents) = child { Some(contents) } else { None })
-					.find(|_| true)?
-					.trim()
-					.split_whitespace()
-					.map(str::to_owned)
-					.collect::<Vec<_>>();
+				let morph_targets = match read_source::<String>(ns, target) {
+					Ok(targets) => targets.values,
+					Err(message) => {
+						eprintln!("warning[collada]: ignoring morph targets of {}: {}", name, message);
+						return None;
+					}
+				};
 
 				Some((name, morph_targets))
 			})
 			.collect::<HashMap<String, Vec<String>>>()
-	}).unwrap_or_else(HashMap::new);
+	}).unwrap_or_default();
 
-	let primary_scene = trim_hash(document.root_element.get_child("scene", ns)
-		.expect("Collada document requires a root scene")
+	let primary_scene = trim_hash(root.get_child("scene", ns)
+		.ok_or_else(|| invalid("Collada document requires a root scene"))?
 		.get_child("instance_visual_scene", ns)
-		.expect("Collada document missing root visual scene")
+		.ok_or_else(|| invalid("Collada document missing root visual scene"))?
 		.get_attribute("url", None)
-		.expect("<instance_visual_scene> missing \"url\" attribute"));
-
+		.ok_or_else(|| invalid("<instance_visual_scene> missing \"url\" attribute"))?);
 
-
-	let nodes = document.root_element.get_child("library_visual_scenes", ns)
-		.expect("Collada document has to have visual scenes")
+	let visual_scene = root.get_child("library_visual_scenes", ns)
+		.ok_or_else(|| invalid("Collada document has to have visual scenes"))?
 		.get_children("visual_scene", ns)
 		.find(|child| child.get_attribute("id", None) == Some(primary_scene))
-		.expect("The scene named in <instance_visual_scene> does not exist")
-		.get_children("node", ns);
+		.ok_or_else(|| invalid("The scene named in <instance_visual_scene> does not exist"))?;
 
 	let mut root_geometry = Vec::new();
 
-	for node in nodes {
+	// Skins attached to the root geometry, along with the skeleton roots named by the <instance_controller>.
+	let mut skin_links = HashMap::new();
+
+	for node in visual_scene.get_children("node", ns) {
+		// Joints are only used to pose skins, and are handled once the skin is known.
 		if node.get_attribute("type", None) == Some("JOINT") {
-			eprintln!("warning[collada]: unsupported node type JOINT, ignoring...");
 			continue;
 		}
 
-		for element in node.children.iter().filter_map(|child| if let &xml::Xml::ElementNode(ref element) = child { Some(element) } else { None }) {
+		for element in node.children.iter().filter_map(|child| if let xml::Xml::ElementNode(ref element) = *child { Some(element) } else { None }) {
 			match &element.name as &str {
 				"asset" => (),
 				"lookat" | "matrix" | "rotate" | "scale" | "skew" | "translate" => {
 					eprintln!("warning[collada]: transformations on nodes are not supported yet (tried to use transformation type: {})...", element.name);
 				},
 				"instance_camera" => eprintln!("warning[collada]: Ignoring instance_camera"),
-				"instance_controller" => eprintln!("warning[collada]: Ignoring instance_controller"),
+				"instance_controller" => {
+					let controller_id = if let Some(url) = element.get_attribute("url", None) {
+						trim_hash(url)
+					} else {
+						eprintln!("warning[collada]: degenerate <instance_controller> is missing a url tag");
+						continue;
+					};
+
+					let controller = if let Some(controller) = find_controller(ns, controllers, controller_id) {
+						controller
+					} else {
+						eprintln!("warning[collada]: <instance_controller> references missing controller {}", controller_id);
+						continue;
+					};
+
+					if let Some(skin) = controller.get_child("skin", ns) {
+						let skin = Skin::read(ns, skin).map_err(|e| invalid(format!("failed to read skin {}: {}", controller_id, e)))?;
+
+						let roots = element.get_children("skeleton", ns)
+							.map(|skeleton| trim_hash(skeleton.content_str().trim()).to_owned())
+							.collect::<Vec<_>>();
+
+						// Skins may be layered on top of a morph, in which case only the base shape is used.
+						let geometry = match find_controller(ns, controllers, &skin.source) {
+							Some(morph) => {
+								eprintln!("warning[collada]: skins applied to morph controllers are unsupported, using the base shape of {}...", skin.source);
+
+								morph.get_child("morph", ns)
+									.and_then(|morph| morph.get_attribute("source", None))
+									.map(|source| trim_hash(source).to_owned())
+									.unwrap_or_else(|| skin.source.clone())
+							},
+							None => skin.source.clone()
+						};
+
+						root_geometry.push(geometry.clone());
+						skin_links.insert(geometry, (skin, roots));
+					} else if let Some(morph) = controller.get_child("morph", ns) {
+						// The frames themselves are found through morph_links.
+						if let Some(source) = morph.get_attribute("source", None) {
+							root_geometry.push(trim_hash(source).to_owned());
+						}
+					} else {
+						eprintln!("warning[collada]: Ignoring controller {}, it is neither a skin nor a morph", controller_id);
+					}
+				},
 				"instance_geometry" => {
 					let object_id = if let Some(url) = element.get_attribute("url", None) {
 						trim_hash(url)
@@ -96,7 +202,7 @@ pub fn convert(document: ColladaDocument) -> V2 {
 				},
 				"instance_light" => eprintln!("warning[collada]: Lights are unsupported"),
 				"instance_node" => eprintln!("warning[collada]: Ignoring instance_node"),
-				"node" => eprintln!("warning[collada]: Nested nodes are unsupported"),
+				"node" if element.get_attribute("type", None) != Some("JOINT") => eprintln!("warning[collada]: Nested nodes are unsupported"),
 				_ => ()
 			}
 		}
@@ -104,62 +210,38 @@ pub fn convert(document: ColladaDocument) -> V2 {
 
 	// Needed information extracted. Now begin conversion.
 
-	if root_geometry.len() == 0 {
-		panic!("No root geometry!");
+	if root_geometry.is_empty() {
+		return Err(invalid("Collada document has no root geometry"));
 	} else if root_geometry.len() > 1 {
 		eprintln!("warning[collada]: ignoring additional root geometry for now, submodels are not supported yet");
 	}
 
 	let root_name = &root_geometry[0];
 
-	let object = objects.get(root_name).expect("geometry library missing root geometry");
-	let object_frames = morph_links.get(root_name)
-		.map(|vec| vec.iter()
-			.map(|name| objects.get(name).expect("geometry library missing geometry frame").clone()).collect::<Vec<Object>>()
-		).unwrap_or_else(Vec::new);
-
-	let mut failed_index = None;
-
-	for (index, frame) in object_frames.iter().enumerate() {
-		if object.vertices.len() != frame.vertices.len() || object.normals.len() != frame.normals.len() || object.tex_vertices.len() != frame.tex_vertices.len() {
-			failed_index = Some(index);
-			break;
-		}
+	let read_geometry = |id: &str| {
+		let geometry = geometries.get_children("geometry", ns)
+			.find(|geometry| geometry.get_attribute("id", None) == Some(id))
+			.ok_or_else(|| invalid(format!("geometry library is missing geometry {}", id)))?;
 
-		let base = &object.geometry;
-		let frame = &frame.geometry;
+		Mesh::read(ns, geometry).map_err(|e| invalid(format!("failed to read geometry {}: {}", id, e)))
+	};
 
-		if base.len() != frame.len() {
-			failed_index = Some(index);
-			break;
-		}
+	let object = read_geometry(root_name)?;
+	let object_frames = match morph_links.get(root_name) {
+		Some(names) => names.iter().map(|name| read_geometry(name)).collect::<io::Result<Vec<Mesh>>>()?,
+		None => Vec::new()
+	};
 
-		if let Some(_) = base.iter().zip(frame.iter()).find(|pair| !compare_geometry(&pair.0.shapes, &pair.1.shapes)) {
-			failed_index = Some(index);
-			break;
-		}
-	}
-
-	if let Some(failed_index) = failed_index {
-		panic!("error[collada]: index {} in the morph target sequence uses different geometry", failed_index);
+	if let Some(failed_index) = object_frames.iter().position(|frame| !object.same_topology(frame)) {
+		return Err(invalid(format!("index {} in the morph target sequence uses different geometry", failed_index)));
 	}
 
 	let mut associations = Vec::new();
 	let mut reverse = HashMap::new();
 	let mut triangles = Vec::new();
 
-	// Note: We make the last entry of each vertex component array the zero/invalid entry for missings
-	let invalid_texture_index = object.tex_vertices.len();
-	let invalid_normal_index = object.normals.len();
-
 	{
-		let mut dedup_vertex = |vertex: VTNIndex| {
-			let vertex = (
-				vertex.0,
-				vertex.1.unwrap_or(invalid_texture_index),
-				vertex.2.unwrap_or(invalid_normal_index)
-			);
-
+		let mut dedup_vertex = |vertex: Corner| {
 			*reverse.entry(vertex).or_insert_with(|| {
 				let index = associations.len();
 
@@ -169,37 +251,70 @@ pub fn convert(document: ColladaDocument) -> V2 {
 			})
 		};
 
-		for geometry in &object.geometry {
-			for shape in &geometry.shapes {
-				match shape {
-					&Shape::Triangle(a, b, c) => {
-						triangles.push((
-							dedup_vertex(a) as u32,
-							dedup_vertex(b) as u32,
-							dedup_vertex(c) as u32
-						));
-					},
-					_ => () // Lines / points unsupported
-				}
-			}
+		for &[a, b, c] in &object.triangles {
+			triangles.push((
+				dedup_vertex(a) as u32,
+				dedup_vertex(b) as u32,
+				dedup_vertex(c) as u32
+			));
 		}
 	}
 
-	println!("{} triangles with {} flattened vertices (from: {} position, {} tex, {} normal)", triangles.len(), associations.len(), object.vertices.len(), object.tex_vertices.len(), object.normals.len());
+	if verbose {
+		eprintln!("{} triangles with {} flattened vertices (from: {} position, {} tex, {} normal)", triangles.len(), associations.len(), object.positions.len(), object.texcoords.len(), object.normals.len());
+	}
 
 	let mut frames = Vec::with_capacity(1 + object_frames.len());
 
 	// TODO: Tag Points
-	let (center, frame0) = extract_frame(&object, &associations, vec![]);
+	let center = if let Some((skin, roots)) = skin_links.get(root_name) {
+		if !object_frames.is_empty() {
+			eprintln!("warning[collada]: geometry has both a skin and morph targets, ignoring the morph targets...");
+		}
 
-	frames.push(frame0);
+		let skeleton = Skeleton::read(ns, &root, visual_scene);
+		let bindings = skin.bind(&skeleton, roots).map_err(invalid)?;
 
-	for additional_frame in &object_frames {
-		frames.push(extract_frame(additional_frame, &associations, vec![]).1);
-	}
+		let duration = skeleton.duration();
+		let baked = (duration * frame_rate).round();
+
+		if baked >= MAX_BAKED_FRAMES as f32 {
+			return Err(invalid(format!("baking {} seconds at {} fps would take more than {} frames", duration, frame_rate, MAX_BAKED_FRAMES)));
+		}
+
+		let frame_count = baked as usize + 1;
+
+		if verbose {
+			eprintln!("baking {} frames ({} seconds at {} fps) from skin {}", frame_count, duration, frame_rate, skin.source);
+		}
+
+		let mut center = None;
+
+		for frame_index in 0..frame_count {
+			let time = (frame_index as f32 / frame_rate).min(duration);
+			let matrices = skin.skinning_matrices(&bindings, &skeleton.pose(time));
 
+			let (frame_center, frame) = extract_frame(&object, &associations, Some((skin, &matrices)), transformation, vec![]);
 
-	v2::V2 {
+			center = center.or(Some(frame_center));
+			frames.push(frame);
+		}
+
+		center.unwrap_or(Point3::new(0.0, 0.0, 0.0))
+	} else {
+		let (center, frame0) = extract_frame(&object, &associations, None, transformation, vec![]);
+
+		frames.push(frame0);
+
+		for additional_frame in &object_frames {
+			frames.push(extract_frame(additional_frame, &associations, None, transformation, vec![]).1);
+		}
+
+		center
+	};
+
+
+	Ok(v2::V2 {
 		center,
 		materials: vec![v2::Material {
 			name: "".to_string(),
@@ -219,27 +334,32 @@ pub fn convert(document: ColladaDocument) -> V2 {
 		],
 		tag_points: vec![],
 		frames
-	}
+	})
 }
 
-fn extract_frame(from: &Object, indices: &[(usize, usize, usize)], tag_points: Vec<Point3<f32>>) -> (Point3<f32>, v2::Frame) {
-	let transformation = Matrix4::from_angle_x(Deg(90.0));
+fn invalid<M>(message: M) -> io::Error where M: Into<String> {
+	io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
 
+fn extract_frame(from: &Mesh, indices: &[Corner], skin: Option<(&Skin, &[Matrix4<f32>])>, transformation: Matrix4<f32>, tag_points: Vec<Point3<f32>>) -> (Point3<f32>, v2::Frame) {
 	let mut vertices = Vec::with_capacity(indices.len());
 	let mut center_builder = collider::CenterBuilder::begin();
 
-	for &(position, texture, normal) in indices {
-		let position = from.vertices[position];
-		let texture = from.tex_vertices.get(texture).unwrap_or(&TVertex { x: 0.0, y: 0.0 });
-		let normal = from.normals.get(normal).unwrap_or(&NVertex { x: 1.0, y: 0.0, z: 0.0 });
+	for &(position_index, normal, texture) in indices {
+		let position = from.positions[position_index];
+		let texture = texture.map(|index| from.texcoords[index]).unwrap_or(Point2 { x: 0.0, y: 0.0 });
+		// Missing normals are left zeroed, to be recalculated once the whole model is known.
+		let normal = normal.map(|index| from.normals[index]).unwrap_or(Vector3::zero());
 
-		let normal = Vector3 { x: normal.x as f32, y: normal.y as f32, z: normal.z as f32 };
-		let position = Point3 { x: position.x as f32, y: position.y as f32, z: position.z as f32 };
+		let (position, normal) = match skin {
+			Some((skin, matrices)) => skin.apply(position_index, position, normal, matrices),
+			None => (position, normal)
+		};
 
 		let vertex = v2::Vertex {
 			position: Point3::from_homogeneous(transformation * position.to_homogeneous()),
-			normal: (transformation * normal.normalize().extend(0.0)).truncate(),
-			texture: Point2 { x: texture.x as f32, y: 1.0 - texture.y as f32 },
+			normal: if normal.is_zero() { normal } else { (transformation * normal.normalize().extend(0.0)).truncate() },
+			texture: Point2 { x: texture.x, y: 1.0 - texture.y },
 		};
 
 		center_builder.update(vertex.position);
@@ -251,58 +371,385 @@ fn extract_frame(from: &Object, indices: &[(usize, usize, usize)], tag_points: V
 	(center, v2::Frame::from_vertices(vertices, tag_points, center))
 }
 
-// Utilities for COLLADA (Mostly taken from private methods in piston_collada)
+/// A corner of a triangle, as indices into the position, normal, and texture coordinate arrays of a mesh.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// The triangulated contents of a `<mesh>`.
+struct Mesh {
+	positions: Vec<Point3<f32>>,
+	normals: Vec<Vector3<f32>>,
+	texcoords: Vec<Point2<f32>>,
+	triangles: Vec<[Corner; 3]>
+}
+
+impl Mesh {
+	fn read(ns: Option<&str>, geometry: &Element) -> Result<Self, String> {
+		let mesh = geometry.get_child("mesh", ns).ok_or("only <mesh> geometry is supported")?;
+		let vertices = mesh.get_child("vertices", ns).ok_or("<mesh> is missing <vertices>")?;
+
+		let positions = get_input(ns, vertices, "POSITION")
+			.and_then(|input| get_input_source(ns, mesh, input))
+			.ok_or_else(|| "<vertices> is missing a POSITION input".to_owned())
+			.and_then(|source| read_source::<f32>(ns, source))?;
+
+		if positions.width < 3 {
+			return Err(format!("positions need 3 components, got {}", positions.width));
+		}
+
+		let mut result = Mesh {
+			positions: positions.elements().map(|p| Point3::new(p[0], p[1], p[2])).collect(),
+			normals: Vec::new(),
+			texcoords: Vec::new(),
+			triangles: Vec::new()
+		};
+
+		// Sources that have already been appended to the normal / texcoord arrays, and where they begin.
+		let mut loaded = HashMap::new();
+
+		// Normals and texture coordinates may also be specified per position as part of <vertices>.
+		let vertex_normals = match get_input(ns, vertices, "NORMAL") {
+			Some(input) => Some(result.load(ns, mesh, input, &mut loaded)?),
+			None => None
+		};
+
+		let vertex_texcoords = match lowest_set(ns, vertices) {
+			Some(input) => Some(result.load(ns, mesh, input, &mut loaded)?),
+			None => None
+		};
+
+		let mut skipped = 0;
+
+		for element in mesh.children.iter().filter_map(|child| if let xml::Xml::ElementNode(ref element) = *child { Some(element) } else { None }) {
+			match &element.name as &str {
+				"triangles" | "polylist" | "polygons" => (),
+				"lines" | "linestrips" | "trifans" | "tristrips" => {
+					skipped += 1;
+					continue;
+				},
+				_ => continue
+			}
+
+			let primitive = Primitive::read(ns, mesh, element, &mut result, &mut loaded)?;
+
+			for polygon in primitive.polygons(ns, element)? {
+				let corners = polygon.chunks(primitive.stride)
+					.map(|corner| primitive.corner(corner, vertex_normals, vertex_texcoords))
+					.collect::<Vec<Corner>>();
+
+				if let Some(corner) = corners.iter().find(|corner| corner.0 >= result.positions.len()) {
+					return Err(format!("position index {} out of range", corner.0));
+				}
 
-fn compare_geometry(base: &[Shape], frame: &[Shape]) -> bool {
-	if base.len() != frame.len() {
-		return false;
+				let positions = corners.iter().map(|corner| result.positions[corner.0]).collect::<Vec<_>>();
+
+				for triangle in triangulate(&positions) {
+					result.triangles.push([corners[triangle[0]], corners[triangle[1]], corners[triangle[2]]]);
+				}
+			}
+		}
+
+		if skipped > 0 {
+			eprintln!("warning[collada]: skipped {} primitive elements, only <triangles>, <polylist>, and <polygons> are supported", skipped);
+		}
+
+		let out_of_range = |index: Option<usize>, len: usize| index.map(|index| index >= len).unwrap_or(false);
+
+		if let Some(corner) = result.triangles.iter().flat_map(|triangle| triangle.iter()).find(|corner| {
+			out_of_range(Some(corner.0), result.positions.len()) || out_of_range(corner.1, result.normals.len()) || out_of_range(corner.2, result.texcoords.len())
+		}) {
+			return Err(format!("index out of range: position {}, normal {:?}, texture {:?}", corner.0, corner.1, corner.2));
+		}
+
+		Ok(result)
 	}
 
-	if let Some(_) = base.iter().zip(frame.iter()).find(|pair| !compare_shape(*pair.0, *pair.1)) {
-		return false;
+	/// Appends the source referenced by a NORMAL or TEXCOORD input to the matching array, returning the index at which
+	/// the source begins. Each source is only loaded once, as separate primitives usually share sources.
+	fn load(&mut self, ns: Option<&str>, mesh: &Element, input: &Element, loaded: &mut HashMap<String, usize>) -> Result<usize, String> {
+		let semantic = input.get_attribute("semantic", None).unwrap_or("");
+		let source = get_input_source(ns, mesh, input)
+			.ok_or_else(|| format!("{} input references missing source {}", semantic, input.get_attribute("source", None).unwrap_or("")))?;
+
+		let key = format!("{}{}", semantic, source.get_attribute("id", None).unwrap_or(""));
+
+		if let Some(&base) = loaded.get(&key) {
+			return Ok(base);
+		}
+
+		let values = read_source::<f32>(ns, source)?;
+
+		let base = match semantic {
+			"NORMAL" if values.width >= 3 => {
+				let base = self.normals.len();
+				self.normals.extend(values.elements().map(|n| Vector3::new(n[0], n[1], n[2])));
+				base
+			},
+			"TEXCOORD" if values.width >= 2 => {
+				let base = self.texcoords.len();
+				self.texcoords.extend(values.elements().map(|t| Point2::new(t[0], t[1])));
+				base
+			},
+			_ => return Err(format!("{} source only has {} components", semantic, values.width))
+		};
+
+		loaded.insert(key, base);
+
+		Ok(base)
 	}
 
-	true
+	/// Checks that another mesh can be used as a morph target of this one.
+	fn same_topology(&self, other: &Mesh) -> bool {
+		self.positions.len() == other.positions.len() &&
+			self.normals.len() == other.normals.len() &&
+			self.texcoords.len() == other.texcoords.len() &&
+			self.triangles == other.triangles
+	}
 }
 
-fn compare_shape(base: Shape, frame: Shape) -> bool {
-	match (base, frame) {
-		(Shape::Point(a), Shape::Point(b)) => a==b,
-		(Shape::Line(a, a1), Shape::Line(b, b1)) => a==b && a1==b1,
-		(Shape::Triangle(a, a1, a2), Shape::Triangle(b, b1, b2)) => a==b && a1==b1 && a2==b2,
-		_ => false
+/// The layout of the indices in a single `<triangles>`, `<polylist>`, or `<polygons>` element.
+struct Primitive {
+	/// Number of indices per corner. Inputs may share an offset, so this is not necessarily the number of inputs.
+	stride: usize,
+	vertex: usize,
+	/// Offset into each corner, and the base index in the mesh's array.
+	normal: Option<(usize, usize)>,
+	texcoord: Option<(usize, usize)>
+}
+
+impl Primitive {
+	fn read(ns: Option<&str>, mesh: &Element, primitive: &Element, result: &mut Mesh, loaded: &mut HashMap<String, usize>) -> Result<Self, String> {
+		let offset = |input: &Element| input.get_attribute("offset", None).and_then(|offset| offset.parse::<usize>().ok()).unwrap_or(0);
+
+		let vertex = get_input(ns, primitive, "VERTEX").map(offset).ok_or_else(|| format!("<{}> is missing a VERTEX input", primitive.name))?;
+
+		let normal = match get_input(ns, primitive, "NORMAL") {
+			Some(input) => Some((offset(input), result.load(ns, mesh, input, loaded)?)),
+			None => None
+		};
+
+		let texcoord = match lowest_set(ns, primitive) {
+			Some(input) => Some((offset(input), result.load(ns, mesh, input, loaded)?)),
+			None => None
+		};
+
+		Ok(Primitive {
+			stride: primitive.get_children("input", ns).map(offset).max().unwrap_or(0).saturating_add(1),
+			vertex,
+			normal,
+			texcoord
+		})
+	}
+
+	/// Splits the index lists of the element into polygons, each of which has a multiple of `stride` indices.
+	fn polygons(&self, ns: Option<&str>, primitive: &Element) -> Result<Vec<Vec<usize>>, String> {
+		let mut polygons = Vec::new();
+
+		match &primitive.name as &str {
+			"triangles" => {
+				let p = read_indices(primitive.get_child("p", ns))?;
+
+				polygons.extend(p.chunks(self.stride.saturating_mul(3)).map(<[usize]>::to_vec));
+			},
+			"polylist" => {
+				let p = read_indices(primitive.get_child("p", ns))?;
+				let mut cursor = 0;
+
+				for count in read_indices(primitive.get_child("vcount", ns))? {
+					let end = count.checked_mul(self.stride).and_then(|len| len.checked_add(cursor)).unwrap_or(usize::MAX);
+
+					polygons.push(p.get(cursor..end).ok_or("<p> is shorter than <vcount> requires")?.to_vec());
+					cursor = end;
+				}
+			},
+			_ => for child in primitive.children.iter().filter_map(|child| if let xml::Xml::ElementNode(ref element) = *child { Some(element) } else { None }) {
+				match &child.name as &str {
+					"p" => polygons.push(read_indices(Some(child))?),
+					"ph" => {
+						eprintln!("warning[collada]: holes in <polygons> are unsupported, filling them in...");
+						polygons.push(read_indices(child.get_child("p", ns))?);
+					},
+					_ => ()
+				}
+			}
+		}
+
+		if let Some(polygon) = polygons.iter().find(|polygon| polygon.len() % self.stride != 0) {
+			return Err(format!("<{}> has a polygon with {} indices, which is not a multiple of {}", primitive.name, polygon.len(), self.stride));
+		}
+
+		Ok(polygons)
+	}
+
+	fn corner(&self, indices: &[usize], vertex_normals: Option<usize>, vertex_texcoords: Option<usize>) -> Corner {
+		let position = indices[self.vertex];
+
+		(
+			position,
+			// Indices that overflow are out of range either way, which is reported once the whole mesh is read.
+			self.normal.map(|(offset, base)| base.saturating_add(indices[offset])).or_else(|| vertex_normals.map(|base| base.saturating_add(position))),
+			self.texcoord.map(|(offset, base)| base.saturating_add(indices[offset])).or_else(|| vertex_texcoords.map(|base| base.saturating_add(position)))
+		)
 	}
 }
 
-fn trim_hash(name: &str) -> &str {
-	if name.starts_with('#') { &name[1..] } else { name }
+/// The contents of a `<source>`, read through its `<accessor>`. Each element holds one value for each named `<param>`.
+pub struct Source<T> {
+	pub values: Vec<T>,
+	pub width: usize
 }
 
-fn get_input<'a>(ns: Option<&'a str>, parent: &'a Element, semantic : &str) -> Option<&'a Element> {
+impl<T> Source<T> {
+	pub fn elements(&self) -> ::std::slice::Chunks<'_, T> {
+		self.values.chunks(self.width.max(1))
+	}
+}
+
+/// Reads a source the way the specification intends: through the count, offset, and stride of the accessor,
+/// skipping any unnamed params.
+pub fn read_source<T: FromStr + Clone>(ns: Option<&str>, source: &Element) -> Result<Source<T>, String> {
+	let id = source.get_attribute("id", None).unwrap_or("");
+
+	let accessor = source.get_child("technique_common", ns).and_then(|technique| technique.get_child("accessor", ns));
+
+	let array = match accessor.and_then(|accessor| accessor.get_attribute("source", None)) {
+		Some(array_id) => source.children.iter()
+			.filter_map(|child| if let xml::Xml::ElementNode(ref element) = *child { Some(element) } else { None })
+			.find(|element| element.get_attribute("id", None) == Some(trim_hash(array_id)))
+			.ok_or_else(|| format!("accessor of source {} references missing array {}", id, array_id))?,
+		None => ["float_array", "int_array", "Name_array", "IDREF_array", "SIDREF_array", "bool_array"].iter()
+			.filter_map(|name| source.get_child(name, ns))
+			.next()
+			.ok_or_else(|| format!("source {} has no array", id))?
+	};
+
+	let array = array.content_str().split_whitespace()
+		.map(|value| value.parse::<T>().map_err(|_| format!("invalid value {:?} in source {}", value, id)))
+		.collect::<Result<Vec<T>, String>>()?;
+
+	let accessor = match accessor {
+		Some(accessor) => accessor,
+		// Without an accessor, the only sensible interpretation is a flat list.
+		None => return Ok(Source { values: array, width: 1 })
+	};
+
+	let attribute = |name: &str, default: usize| accessor.get_attribute(name, None)
+		.map(|value| value.parse::<usize>().map_err(|_| format!("accessor of source {} has an invalid {}", id, name)))
+		.unwrap_or(Ok(default));
+
+	let count = attribute("count", 0)?;
+	let offset = attribute("offset", 0)?;
+	let stride = attribute("stride", 1)?;
+
+	// Each param covers one or more values, and only named params are read.
+	let mut params = Vec::new();
+	let mut position = 0usize;
+
+	for param in accessor.get_children("param", ns) {
+		let size = param_size(param.get_attribute("type", None).unwrap_or("float"));
+
+		if param.get_attribute("name", None).is_some() {
+			params.push(position..position.saturating_add(size));
+		}
+
+		position = position.saturating_add(size);
+	}
+
+	if params.is_empty() {
+		params.push(0..stride);
+	}
+
+	// Checking the end of the last element up front bounds the count by the length of the array.
+	let needed = params.iter().map(|range| range.end).max().unwrap_or(0);
+	let end = match count {
+		0 => Some(0),
+		_ if stride == 0 => None,
+		count => (count - 1).checked_mul(stride).and_then(|start| start.checked_add(offset)).and_then(|start| start.checked_add(needed))
+	};
+
+	if end.map(|end| end > array.len()).unwrap_or(true) {
+		return Err(format!("accessor of source {} reads past the end of its array", id));
+	}
+
+	let width = params.iter().map(|range| range.len()).sum();
+	let mut values = Vec::with_capacity(count * width);
+
+	for element in 0..count {
+		let base = offset + element * stride;
+
+		for range in &params {
+			let values_range = base + range.start..base + range.end;
+
+			values.extend_from_slice(array.get(values_range).ok_or_else(|| format!("accessor of source {} reads past the end of its array", id))?);
+		}
+	}
+
+	Ok(Source { values, width })
+}
+
+/// Number of array values covered by a param of the given type, such as 16 for `float4x4` or 3 for `float3`.
+fn param_size(ty: &str) -> usize {
+	let dimensions = ty.trim_start_matches(|c: char| !c.is_ascii_digit());
+
+	match dimensions.split_once('x') {
+		Some((rows, columns)) => rows.parse::<usize>().ok().and_then(|rows| rows.checked_mul(columns.parse::<usize>().ok()?)),
+		None => dimensions.parse::<usize>().ok()
+	}.unwrap_or(1)
+}
+
+/// Picks the TEXCOORD input with the lowest set, as CEM only supports a single set of texture coordinates.
+fn lowest_set<'a>(ns: Option<&'a str>, parent: &'a Element) -> Option<&'a Element> {
+	let set = |input: &Element| input.get_attribute("set", None).and_then(|set| set.parse::<u32>().ok()).unwrap_or(0);
+
+	let mut inputs = parent.get_children("input", ns)
+		.filter(|input| input.get_attribute("semantic", None) == Some("TEXCOORD"))
+		.collect::<Vec<_>>();
+
+	inputs.sort_by_key(|input| set(input));
+
+	if inputs.len() > 1 {
+		eprintln!("warning[collada]: only one set of texture coordinates is supported, using set {} and ignoring {} others", set(inputs[0]), inputs.len() - 1);
+	}
+
+	inputs.first().cloned()
+}
+
+fn read_indices(element: Option<&Element>) -> Result<Vec<usize>, String> {
+	element.map(Element::content_str).unwrap_or_default()
+		.split_whitespace()
+		.map(|value| value.parse::<usize>().map_err(|_| format!("invalid index {:?}", value)))
+		.collect()
+}
+
+// Utilities for COLLADA (Mostly taken from private methods in piston_collada)
+
+fn find_controller<'a>(ns: Option<&'a str>, controllers: Option<&'a Element>, id: &str) -> Option<&'a Element> {
+	controllers?.get_children("controller", ns).find(|controller| controller.get_attribute("id", None) == Some(id))
+}
+
+pub fn trim_hash(name: &str) -> &str {
+	if let Some(stripped) = name.strip_prefix('#') { stripped } else { name }
+}
+
+pub fn get_input<'a>(ns: Option<&'a str>, parent: &'a Element, semantic : &str) -> Option<&'a Element> {
 	let mut inputs = parent.get_children("input", ns);
 	inputs.find( |i| i.get_attribute("semantic", None) == Some(semantic))
 }
 
-fn get_input_source<'a>(ns: Option<&'a str>, parent_element: &'a Element, input_element: &'a Element) -> Option<&'a Element> {
-	let source_id = input_element.get_attribute("source", None)?;
-
-	if let Some(element) = parent_element.children.iter()
-		.filter_map(|node| { if let &xml::Xml::ElementNode(ref e) = node { Some(e) } else { None } })
-		.find(|e| {
-			if let Some(id) = e.get_attribute("id", None) {
-				let id = "#".to_string() + id;
-				id == source_id
-			} else {
-				false
-			}
-		})
-		{
-			if element.name == "source" {
-				return Some(element);
-			} else {
-				let input = element.get_child("input", ns)?;
-				return get_input_source(ns, parent_element, input);
-			}
-		}
-	None
-}
\ No newline at end of file
+pub fn get_input_source<'a>(ns: Option<&'a str>, parent_element: &'a Element, input_element: &'a Element) -> Option<&'a Element> {
+	let find = |input: &Element| {
+		let source_id = trim_hash(input.get_attribute("source", None)?);
+
+		parent_element.children.iter()
+			.filter_map(|node| { if let xml::Xml::ElementNode(ref e) = *node { Some(e) } else { None } })
+			.find(|e| e.get_attribute("id", None) == Some(source_id))
+	};
+
+	let element = find(input_element)?;
+
+	if element.name == "source" {
+		Some(element)
+	} else {
+		// Inputs may refer to <vertices>, which is followed only once as it could otherwise refer back to itself.
+		find(element.get_child("input", ns)?).filter(|source| source.name == "source")
+	}
+}
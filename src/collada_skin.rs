@@ -0,0 +1,507 @@
+use cgmath::{Point3, Vector3, Vector4, Matrix4, Deg, InnerSpace, SquareMatrix};
+use collada_import::{trim_hash, get_input, get_input_source, read_source};
+use std::str::FromStr;
+use xml::{self, Element};
+
+/// A `<skin>` controller. Binds each entry of a geometry's position array to a weighted set of joints.
+pub struct Skin {
+	/// ID of the geometry being skinned
+	pub source: String,
+	bind_shape_matrix: Matrix4<f32>,
+	/// Joint names, either SIDs (Name_array) or IDs (IDREF_array) of nodes in the visual scene
+	joints: Vec<String>,
+	inverse_bind_matrices: Vec<Matrix4<f32>>,
+	/// Influences for each position, as (joint, weight). A joint of None refers to the bind shape itself.
+	influences: Vec<Vec<(Option<usize>, f32)>>
+}
+
+impl Skin {
+	pub fn read(ns: Option<&str>, skin: &Element) -> Result<Self, String> {
+		let source = trim_hash(skin.get_attribute("source", None).ok_or("<skin> is missing a \"source\" attribute")?).to_owned();
+
+		let bind_shape_matrix = match skin.get_child("bind_shape_matrix", ns) {
+			Some(element) => match parse_list::<f32>(&element.content_str())? {
+				ref values if values.len() == 16 => row_major(values),
+				values => return Err(format!("<bind_shape_matrix> needs 16 values, got {}", values.len()))
+			},
+			None => Matrix4::identity()
+		};
+
+		let joints_element = skin.get_child("joints", ns).ok_or("<skin> is missing <joints>")?;
+
+		let joints = get_input(ns, joints_element, "JOINT")
+			.and_then(|input| get_input_source(ns, skin, input))
+			.ok_or_else(|| "<joints> is missing a JOINT input".to_owned())
+			.and_then(|source| read_source::<String>(ns, source))?
+			.values;
+
+		let inverse_bind_matrices = get_input(ns, joints_element, "INV_BIND_MATRIX")
+			.and_then(|input| get_input_source(ns, skin, input))
+			.ok_or_else(|| "<joints> is missing an INV_BIND_MATRIX input".to_owned())
+			.and_then(|source| read_source::<f32>(ns, source))
+			.and_then(|source| if source.width == 16 {
+				Ok(source.elements().map(row_major).collect::<Vec<_>>())
+			} else {
+				Err(format!("INV_BIND_MATRIX source has {} values per element instead of 16", source.width))
+			})?;
+
+		if inverse_bind_matrices.len() != joints.len() {
+			return Err(format!("skin has {} joints, but {} inverse bind matrices", joints.len(), inverse_bind_matrices.len()));
+		}
+
+		let vertex_weights = skin.get_child("vertex_weights", ns).ok_or("<skin> is missing <vertex_weights>")?;
+
+		let joint_offset = get_offset(ns, vertex_weights, "JOINT").ok_or("<vertex_weights> is missing a JOINT input")?;
+		let weight_offset = get_offset(ns, vertex_weights, "WEIGHT").ok_or("<vertex_weights> is missing a WEIGHT input")?;
+		let stride = joint_offset.max(weight_offset).saturating_add(1);
+
+		let weights = get_input(ns, vertex_weights, "WEIGHT")
+			.and_then(|input| get_input_source(ns, skin, input))
+			.ok_or_else(|| "<vertex_weights> is missing a WEIGHT source".to_owned())
+			.and_then(|source| read_source::<f32>(ns, source))?
+			.values;
+
+		let vcount = vertex_weights.get_child("vcount", ns).map(|element| parse_list::<usize>(&element.content_str())).unwrap_or_else(|| Ok(Vec::new()))?;
+		let v = vertex_weights.get_child("v", ns).map(|element| parse_list::<i64>(&element.content_str())).unwrap_or_else(|| Ok(Vec::new()))?;
+
+		let mut influences = Vec::with_capacity(vcount.len());
+		let mut cursor = 0usize;
+
+		for count in vcount {
+			let mut vertex = Vec::with_capacity(count.min(v.len()));
+
+			for _ in 0..count {
+				let joint = *v.get(cursor.saturating_add(joint_offset)).ok_or("<v> is shorter than <vcount> requires")?;
+				let weight = *v.get(cursor.saturating_add(weight_offset)).ok_or("<v> is shorter than <vcount> requires")?;
+
+				let joint = match joint {
+					-1 => None,
+					joint if joint >= 0 && (joint as usize) < joints.len() => Some(joint as usize),
+					joint => return Err(format!("vertex weight references joint {}, but there are only {} joints", joint, joints.len()))
+				};
+
+				let weight = if weight >= 0 { weights.get(weight as usize).cloned() } else { None }
+					.ok_or_else(|| format!("vertex weight references missing weight {}", weight))?;
+
+				vertex.push((joint, weight));
+				cursor = cursor.saturating_add(stride);
+			}
+
+			// Weights are supposed to be normalized already, but not every exporter bothers.
+			let total: f32 = vertex.iter().map(|&(_, weight)| weight).sum();
+
+			if total > 0.0 {
+				for influence in &mut vertex {
+					influence.1 /= total;
+				}
+			} else {
+				vertex.clear();
+			}
+
+			influences.push(vertex);
+		}
+
+		Ok(Skin {
+			source,
+			bind_shape_matrix,
+			joints,
+			inverse_bind_matrices,
+			influences
+		})
+	}
+
+	/// Finds the node in the skeleton that drives each joint of this skin. Joints are looked up by SID beneath the
+	/// skeleton roots first, then by ID and name anywhere in the scene.
+	pub fn bind(&self, skeleton: &Skeleton, roots: &[String]) -> Result<Vec<usize>, String> {
+		let roots = roots.iter()
+			.filter_map(|root| skeleton.nodes.iter().position(|node| node.id.as_ref() == Some(root)))
+			.collect::<Vec<_>>();
+
+		self.joints.iter().map(|joint| {
+			let under_roots = |index: usize| roots.is_empty() || roots.iter().any(|&root| skeleton.is_descendant(index, root));
+
+			skeleton.nodes.iter().enumerate().position(|(index, node)| node.sid.as_ref() == Some(joint) && under_roots(index))
+				.or_else(|| skeleton.nodes.iter().position(|node| node.id.as_ref() == Some(joint)))
+				.or_else(|| skeleton.nodes.iter().position(|node| node.name.as_ref() == Some(joint)))
+				.ok_or_else(|| format!("skin references joint {:?}, which is not in the visual scene", joint))
+		}).collect()
+	}
+
+	/// Computes the matrix that moves a vertex from the bind shape into the current pose for each joint.
+	pub fn skinning_matrices(&self, bindings: &[usize], world: &[Matrix4<f32>]) -> Vec<Matrix4<f32>> {
+		bindings.iter()
+			.zip(self.inverse_bind_matrices.iter())
+			.map(|(&node, inverse_bind)| world[node] * inverse_bind * self.bind_shape_matrix)
+			.collect()
+	}
+
+	/// Applies the skin to a single vertex. The position index selects the influences, as these are specified
+	/// per entry in the geometry's position array.
+	pub fn apply(&self, position_index: usize, position: Point3<f32>, normal: Vector3<f32>, matrices: &[Matrix4<f32>]) -> (Point3<f32>, Vector3<f32>) {
+		let influences = match self.influences.get(position_index) {
+			Some(influences) if !influences.is_empty() => influences,
+			_ => return (
+				Point3::from_homogeneous(self.bind_shape_matrix * position.to_homogeneous()),
+				(self.bind_shape_matrix * normal.extend(0.0)).truncate()
+			)
+		};
+
+		let mut skinned_position = Vector4::new(0.0, 0.0, 0.0, 0.0);
+		let mut skinned_normal = Vector3::new(0.0, 0.0, 0.0);
+
+		for &(joint, weight) in influences {
+			let matrix = joint.map(|joint| matrices[joint]).unwrap_or(self.bind_shape_matrix);
+
+			skinned_position += matrix * position.to_homogeneous() * weight;
+			skinned_normal += (matrix * normal.extend(0.0)).truncate() * weight;
+		}
+
+		let skinned_normal = if skinned_normal.magnitude2() > 0.0 { skinned_normal.normalize() } else { normal };
+
+		(Point3::from_homogeneous(skinned_position), skinned_normal)
+	}
+}
+
+/// The node hierarchy of a visual scene, along with any animations targeting the transforms of the nodes.
+pub struct Skeleton {
+	nodes: Vec<Node>,
+	channels: Vec<Channel>
+}
+
+impl Skeleton {
+	pub fn read(ns: Option<&str>, root: &Element, visual_scene: &Element) -> Self {
+		let mut nodes = Vec::new();
+
+		for node in visual_scene.get_children("node", ns) {
+			read_node(ns, node, None, &mut nodes);
+		}
+
+		let mut skeleton = Skeleton {
+			nodes,
+			channels: Vec::new()
+		};
+
+		if let Some(animations) = root.get_child("library_animations", ns) {
+			for animation in animations.get_children("animation", ns) {
+				skeleton.read_animation(ns, animation);
+			}
+		}
+
+		skeleton
+	}
+
+	fn read_animation(&mut self, ns: Option<&str>, animation: &Element) {
+		for channel in animation.get_children("channel", ns) {
+			let (source, target) = match (channel.get_attribute("source", None), channel.get_attribute("target", None)) {
+				(Some(source), Some(target)) => (trim_hash(source), target),
+				_ => {
+					eprintln!("warning[collada]: degenerate <channel> is missing a source or target");
+					continue;
+				}
+			};
+
+			let (node, transform, member) = match self.resolve_target(target) {
+				Some(resolved) => resolved,
+				None => continue
+			};
+
+			let sampler = animation.get_children("sampler", ns)
+				.find(|sampler| sampler.get_attribute("id", None) == Some(source))
+				.ok_or_else(|| format!("channel references missing sampler {:?}", source))
+				.and_then(|sampler| Sampler::read(ns, animation, sampler));
+
+			match sampler {
+				Ok(sampler) => self.channels.push(Channel { node, transform, member, sampler }),
+				Err(message) => eprintln!("warning[collada]: ignoring animation channel targeting {:?}: {}", target, message)
+			}
+		}
+
+		// Animations may be grouped into nested animations.
+		for child in animation.get_children("animation", ns) {
+			self.read_animation(ns, child);
+		}
+	}
+
+	/// Resolves a target address in the form `node/sid`, `node/sid.MEMBER`, or `node/sid(index)`.
+	fn resolve_target(&self, target: &str) -> Option<(usize, usize, Member)> {
+		let (node_id, path) = target.split_once('/')?;
+
+		// Only nodes that are part of the visual scene can be posed, animations of anything else are ignored silently.
+		let node = self.nodes.iter().position(|node| node.id.as_ref().map(String::as_ref) == Some(node_id))?;
+
+		let (sid, member) = if let Some(dot) = path.find('.') {
+			let member = match &path[dot + 1..] {
+				"X" | "S" | "U" | "R" => Member::Index(0),
+				"Y" | "T" | "V" | "G" => Member::Index(1),
+				"Z" | "P" | "B" => Member::Index(2),
+				"ANGLE" | "W" | "Q" | "A" => Member::Index(3),
+				other => {
+					eprintln!("warning[collada]: unsupported animation target member {:?} in {:?}", other, target);
+					return None;
+				}
+			};
+
+			(&path[..dot], member)
+		} else if let Some(paren) = path.find('(') {
+			let indices = path[paren..].split(['(', ')']).filter(|s| !s.is_empty()).collect::<Vec<_>>();
+
+			match (indices.len(), indices.first().and_then(|index| index.parse::<usize>().ok())) {
+				(1, Some(index)) => (&path[..paren], Member::Index(index)),
+				_ => {
+					eprintln!("warning[collada]: unsupported animation target {:?}, only single indices are supported", target);
+					return None;
+				}
+			}
+		} else {
+			(path, Member::All)
+		};
+
+		let transform = self.nodes[node].transforms.iter().position(|transform| transform.sid.as_ref().map(String::as_ref) == Some(sid));
+
+		if transform.is_none() {
+			eprintln!("warning[collada]: animation targets {:?}, but node {:?} has no such transform", target, node_id);
+		}
+
+		Some((node, transform?, member))
+	}
+
+	fn is_descendant(&self, mut index: usize, ancestor: usize) -> bool {
+		loop {
+			if index == ancestor {
+				return true;
+			}
+
+			match self.nodes[index].parent {
+				Some(parent) => index = parent,
+				None => return false
+			}
+		}
+	}
+
+	/// Time of the final keyframe across all animations, in seconds.
+	pub fn duration(&self) -> f32 {
+		self.channels.iter()
+			.filter_map(|channel| channel.sampler.input.last().cloned())
+			.fold(0.0, f32::max)
+	}
+
+	/// Computes the world transform of every node at the specified time.
+	pub fn pose(&self, time: f32) -> Vec<Matrix4<f32>> {
+		let mut world: Vec<Matrix4<f32>> = Vec::with_capacity(self.nodes.len());
+
+		for (index, node) in self.nodes.iter().enumerate() {
+			let mut local = Matrix4::identity();
+
+			for (transform_index, transform) in node.transforms.iter().enumerate() {
+				let mut values = transform.values.clone();
+
+				for channel in self.channels.iter().filter(|channel| channel.node == index && channel.transform == transform_index) {
+					let sampled = channel.sampler.sample(time);
+
+					match channel.member {
+						Member::All => for (value, sampled) in values.iter_mut().zip(sampled) {
+							*value = sampled;
+						},
+						Member::Index(member) => if let (Some(value), Some(&sampled)) = (values.get_mut(member), sampled.first()) {
+							*value = sampled;
+						}
+					}
+				}
+
+				local = local * transform.kind.matrix(&values);
+			}
+
+			// Nodes are stored in pre-order, so the parent is always computed before the child.
+			world.push(match node.parent {
+				Some(parent) => world[parent] * local,
+				None => local
+			});
+		}
+
+		world
+	}
+}
+
+struct Node {
+	id: Option<String>,
+	sid: Option<String>,
+	name: Option<String>,
+	parent: Option<usize>,
+	transforms: Vec<TransformElement>
+}
+
+fn read_node(ns: Option<&str>, element: &Element, parent: Option<usize>, nodes: &mut Vec<Node>) {
+	let index = nodes.len();
+	let mut transforms = Vec::new();
+
+	for child in element.children.iter().filter_map(|child| if let xml::Xml::ElementNode(ref element) = *child { Some(element) } else { None }) {
+		let kind = match &child.name as &str {
+			"matrix" => TransformKind::Matrix,
+			"translate" => TransformKind::Translate,
+			"rotate" => TransformKind::Rotate,
+			"scale" => TransformKind::Scale,
+			"lookat" | "skew" => {
+				eprintln!("warning[collada]: unsupported transformation type {} on a node, ignoring...", child.name);
+				continue;
+			},
+			_ => continue
+		};
+
+		match parse_list::<f32>(&child.content_str()) {
+			Ok(ref values) if values.len() == kind.len() => transforms.push(TransformElement {
+				sid: child.get_attribute("sid", None).map(str::to_owned),
+				kind,
+				values: values.clone()
+			}),
+			_ => eprintln!("warning[collada]: malformed <{}> on a node, ignoring...", child.name)
+		}
+	}
+
+	nodes.push(Node {
+		id: element.get_attribute("id", None).map(str::to_owned),
+		sid: element.get_attribute("sid", None).map(str::to_owned),
+		name: element.get_attribute("name", None).map(str::to_owned),
+		parent,
+		transforms
+	});
+
+	for child in element.get_children("node", ns) {
+		read_node(ns, child, Some(index), nodes);
+	}
+}
+
+struct TransformElement {
+	sid: Option<String>,
+	kind: TransformKind,
+	values: Vec<f32>
+}
+
+#[derive(Copy, Clone)]
+enum TransformKind {
+	Matrix,
+	Translate,
+	Rotate,
+	Scale
+}
+
+impl TransformKind {
+	fn len(&self) -> usize {
+		match *self {
+			TransformKind::Matrix => 16,
+			TransformKind::Translate | TransformKind::Scale => 3,
+			TransformKind::Rotate => 4
+		}
+	}
+
+	fn matrix(&self, values: &[f32]) -> Matrix4<f32> {
+		match *self {
+			TransformKind::Matrix => row_major(values),
+			TransformKind::Translate => Matrix4::from_translation(Vector3::new(values[0], values[1], values[2])),
+			TransformKind::Rotate => {
+				let axis = Vector3::new(values[0], values[1], values[2]);
+
+				if axis.magnitude2() > 0.0 {
+					Matrix4::from_axis_angle(axis.normalize(), Deg(values[3]))
+				} else {
+					Matrix4::identity()
+				}
+			},
+			TransformKind::Scale => Matrix4::from_nonuniform_scale(values[0], values[1], values[2])
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+enum Member {
+	All,
+	Index(usize)
+}
+
+struct Channel {
+	node: usize,
+	transform: usize,
+	member: Member,
+	sampler: Sampler
+}
+
+struct Sampler {
+	input: Vec<f32>,
+	output: Vec<f32>,
+	stride: usize,
+	step: bool
+}
+
+impl Sampler {
+	fn read(ns: Option<&str>, animation: &Element, sampler: &Element) -> Result<Self, String> {
+		let read = |semantic: &str| {
+			get_input(ns, sampler, semantic)
+				.and_then(|input| get_input_source(ns, animation, input))
+				.ok_or_else(|| format!("sampler is missing an {} input", semantic))
+		};
+
+		let input = read_source::<f32>(ns, read("INPUT")?)?.values;
+		let output = read_source::<f32>(ns, read("OUTPUT")?)?;
+
+		if input.is_empty() || output.values.len() != input.len() * output.width {
+			return Err(format!("sampler has {} keyframes, but {} output values", input.len(), output.values.len()));
+		}
+
+		let interpolation = match read("INTERPOLATION") {
+			Ok(source) => read_source::<String>(ns, source)?.values,
+			Err(_) => Vec::new()
+		};
+
+		if interpolation.iter().any(|name| name != "LINEAR" && name != "STEP") {
+			eprintln!("warning[collada]: only LINEAR and STEP interpolation are supported, treating other curves as LINEAR...");
+		}
+
+		Ok(Sampler {
+			stride: output.width,
+			step: !interpolation.is_empty() && interpolation.iter().all(|name| name == "STEP"),
+			input,
+			output: output.values
+		})
+	}
+
+	fn key(&self, index: usize) -> &[f32] {
+		&self.output[index * self.stride..(index + 1) * self.stride]
+	}
+
+	fn sample(&self, time: f32) -> Vec<f32> {
+		let next = self.input.iter().position(|&key| key > time).unwrap_or(self.input.len());
+
+		if next == 0 {
+			return self.key(0).to_vec();
+		} else if next == self.input.len() || self.step {
+			return self.key(next - 1).to_vec();
+		}
+
+		let (start, end) = (self.input[next - 1], self.input[next]);
+		let factor = (time - start) / (end - start);
+
+		self.key(next - 1).iter()
+			.zip(self.key(next))
+			.map(|(&a, &b)| a + (b - a) * factor)
+			.collect()
+	}
+}
+
+fn get_offset(ns: Option<&str>, parent: &Element, semantic: &str) -> Option<usize> {
+	get_input(ns, parent, semantic)?.get_attribute("offset", None)?.parse().ok()
+}
+
+fn parse_list<T: FromStr>(text: &str) -> Result<Vec<T>, String> {
+	text.split_whitespace()
+		.map(|value| value.parse::<T>().map_err(|_| format!("invalid number {:?}", value)))
+		.collect()
+}
+
+/// COLLADA stores matrices in row-major order, while cgmath expects columns.
+fn row_major(m: &[f32]) -> Matrix4<f32> {
+	Matrix4::new(
+		m[0], m[4], m[8],  m[12],
+		m[1], m[5], m[9],  m[13],
+		m[2], m[6], m[10], m[14],
+		m[3], m[7], m[11], m[15]
+	)
+}
@@ -0,0 +1,272 @@
+use cem::{ModelHeader, V2, Scene, Model, Encode};
+use cgmath::Matrix4;
+use bounds;
+use collada_export;
+use collada_import::{self, UpAxis};
+use layout;
+use normals::{self, NormalMode};
+use obj_export;
+use obj_import;
+use optimize;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use text;
+use textures::{self, TextureDir};
+use transform;
+use validate;
+use weld;
+use xml;
+
+/// A file format along with the options that apply to it.
+#[derive(Debug, Clone)]
+pub enum Format {
+	Cem(CemFormat),
+	Obj(ObjFormat),
+	Collada(ColladaFormat),
+	Json,
+	Ron
+}
+
+/// Options for CEM files. When writing, `verbose` prints what the processing passes changed, and a transform also
+/// recomputes the bounds.
+#[derive(Debug, Clone)]
+pub struct CemFormat {
+	pub version: (u16, u16),
+	pub normals: Option<NormalMode>,
+	pub transform: Option<Matrix4<f32>>,
+	pub weld: Option<f32>,
+	pub optimize: bool,
+	pub recompute_bounds: bool,
+	pub verbose: bool
+}
+
+impl Default for CemFormat {
+	/// CEM version 2.0 without any of the processing passes.
+	fn default() -> Self {
+		CemFormat { version: (2, 0), normals: None, transform: None, weld: None, optimize: false, recompute_bounds: false, verbose: false }
+	}
+}
+
+/// Options for OBJ files. When reading, `verbose` prints what was imported. When writing with a texture directory,
+/// the textures are placed next to the output, and a material library is always written.
+#[derive(Debug, Clone, Default)]
+pub struct ObjFormat {
+	pub frame_index: usize,
+	pub mtl: bool,
+	pub debug_bounds: bool,
+	pub textures: Option<TextureDir>,
+	pub verbose: bool
+}
+
+/// Options for COLLADA files. The frame rate and up axis are used when reading, and `verbose` prints what was
+/// imported. The textures are placed next to the output when writing with a texture directory.
+#[derive(Debug, Clone)]
+pub struct ColladaFormat {
+	pub frame_rate: f32,
+	pub up_axis: Option<UpAxis>,
+	pub debug_bounds: bool,
+	pub textures: Option<TextureDir>,
+	pub verbose: bool
+}
+
+impl Default for ColladaFormat {
+	fn default() -> Self {
+		ColladaFormat { frame_rate: 30.0, up_axis: None, debug_bounds: false, textures: None, verbose: false }
+	}
+}
+
+impl Format {
+	/// CEM version 2.0 without any of the processing passes.
+	pub fn cem() -> Self {
+		Format::Cem(CemFormat::default())
+	}
+
+	/// OBJ files with the default options.
+	pub fn obj() -> Self {
+		Format::Obj(ObjFormat::default())
+	}
+
+	/// COLLADA files with the default options.
+	pub fn collada() -> Self {
+		Format::Collada(ColladaFormat::default())
+	}
+
+	/// Extension of files written in this format.
+	pub fn extension(&self) -> &'static str {
+		match *self {
+			Format::Cem(_) => "cem",
+			Format::Obj(_) => "obj",
+			Format::Collada(_) => "dae",
+			Format::Json => "json",
+			Format::Ron => "ron"
+		}
+	}
+
+	/// Extensions of the files searched for when converting a directory from this format.
+	pub fn input_extensions(&self) -> &'static [&'static str] {
+		match *self {
+			Format::Cem(_) => &["cem", "ssmf"],
+			Format::Obj(_) => &["obj"],
+			Format::Collada(_) => &["dae"],
+			Format::Json => &["json"],
+			Format::Ron => &["ron"]
+		}
+	}
+}
+
+/// Reads a CEMv2 scene, naming the subcommand in the error for other versions. The layout of the file is checked
+/// before decoding, so corrupt counts fail with an error instead of exhausting memory.
+pub fn read_scene<R>(mut input: R, action: &str) -> io::Result<Scene<V2>> where R: Read {
+	let header = ModelHeader::read(&mut input)?;
+
+	if header != V2::HEADER {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Cannot {} CEM version {}.{} files yet, only 2.0 is supported", action, header.major, header.minor)));
+	}
+
+	let mut bytes = Vec::new();
+	input.read_to_end(&mut bytes)?;
+
+	layout::check(&bytes)?;
+
+	Scene::<V2>::read_without_header(&mut &bytes[..])
+}
+
+/// Reads the input in any format and writes it in any other. The output path is used to place files written
+/// alongside the output, like OBJ material libraries, and is None when writing to stdout.
+pub fn convert<I, O>(i: I, o: O, output: Option<&Path>, input_format: &Format, format: &Format) -> io::Result<()> where I: Read, O: Write {
+	let scene = read(i, input_format)?;
+
+	validate::check(&scene)?;
+
+	write(scene, o, output, format)
+}
+
+/// Reads a scene in any format, without checking whether it is valid. Normals missing from OBJ and COLLADA files are
+/// generated, other formats are read as they are.
+pub fn read<I>(mut i: I, format: &Format) -> io::Result<Scene<V2>> where I: Read {
+	match *format {
+		Format::Cem(_) => read_scene(i, "convert"),
+		Format::Obj(ObjFormat { verbose, .. }) => {
+			let mut buffer = String::new();
+			i.read_to_string(&mut buffer)?;
+
+			Ok(generate_missing_normals(Scene::root(obj_import::convert(&buffer, verbose)?)))
+		},
+		Format::Collada(ColladaFormat { frame_rate, up_axis, verbose, .. }) => {
+			let mut buffer = String::new();
+			i.read_to_string(&mut buffer)?;
+
+			let xml = buffer.parse::<xml::Element>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}", e)))?;
+
+			Ok(generate_missing_normals(Scene::root(collada_import::convert(xml, frame_rate, up_axis, verbose)?)))
+		},
+		Format::Json => text::read_json(i),
+		Format::Ron => text::read_ron(i)
+	}
+}
+
+/// Writes a scene in any format, applying the processing passes of CEM output. The output path is used as in
+/// `convert`.
+pub fn write<O>(mut scene: Scene<V2>, mut o: O, output: Option<&Path>, format: &Format) -> io::Result<()> where O: Write {
+	match *format {
+		Format::Cem(CemFormat { version: (2, 0), normals, transform, weld, optimize, recompute_bounds, verbose }) => {
+			if let Some(matrix) = transform {
+				transform::apply(&mut scene, matrix);
+			}
+
+			if let Some(epsilon) = weld {
+				let welded = weld::weld(&mut scene, epsilon);
+
+				if verbose {
+					eprintln!(
+						"welded {} vertices into {}, saving {}, and removed {} degenerate and {} duplicate triangles",
+						welded.vertices_before, welded.vertices_after, welded.vertices_before - welded.vertices_after, welded.degenerate, welded.duplicate
+					);
+				}
+			}
+
+			update_normals(&mut scene, normals);
+
+			if optimize {
+				optimize::optimize(&mut scene, verbose);
+			}
+
+			if recompute_bounds || transform.is_some() {
+				bounds::recompute(&mut scene, verbose);
+			}
+
+			scene.write(&mut o)
+		},
+		Format::Cem(CemFormat { version: (major, minor), .. }) => {
+			Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot write CEM version {}.{} files yet, only 2.0 is supported", major, minor)))
+		},
+		Format::Obj(ObjFormat { frame_index, mtl, debug_bounds, ref textures, .. }) => {
+			package_textures(&mut scene, textures.as_ref(), output)?;
+
+			let mtl = if mtl || textures.is_some() {
+				let path = match output {
+					Some(output) => output.with_extension("mtl"),
+					None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "writing a material library requires an output file"))
+				};
+
+				fs::write(&path, obj_export::mtl(&scene.model))?;
+
+				path.file_name().map(|name| name.to_string_lossy().into_owned())
+			} else {
+				None
+			};
+
+			let obj = obj_export::convert(&scene.model, frame_index, mtl.as_ref().map(|name| name as &str), debug_bounds)?;
+
+			o.write_all(obj.as_bytes())
+		},
+		Format::Collada(ColladaFormat { up_axis, debug_bounds, ref textures, .. }) => {
+			package_textures(&mut scene, textures.as_ref(), output)?;
+
+			collada_export::convert(scene, &mut o, up_axis.unwrap_or(UpAxis::Y), debug_bounds)
+		},
+		Format::Json => text::write_json(&scene, o),
+		Format::Ron => text::write_ron(&scene, o)
+	}
+}
+
+/// Places the textures of the scene next to the output file, if there is a texture directory to find them in.
+fn package_textures(scene: &mut Scene<V2>, textures: Option<&TextureDir>, output: Option<&Path>) -> io::Result<()> {
+	let textures = match textures {
+		Some(textures) => textures,
+		None => return Ok(())
+	};
+
+	let dir = match output {
+		Some(output) => output.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new(".")),
+		None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "placing textures next to the output requires an output file"))
+	};
+
+	textures::package(scene, textures, dir)
+}
+
+/// Recalculates the normals of the scene and its children if requested. Otherwise the geometry is left alone.
+pub fn update_normals(scene: &mut Scene<V2>, mode: Option<NormalMode>) {
+	let mode = match mode {
+		Some(mode) => mode,
+		None => return
+	};
+
+	normals::recalculate(&mut scene.model, mode);
+
+	for child in &mut scene.children {
+		update_normals(child, Some(mode));
+	}
+}
+
+/// Generates smooth normals for an imported model if some of its vertices have none, as the importers leave them
+/// zeroed until the whole model is known.
+fn generate_missing_normals(mut scene: Scene<V2>) -> Scene<V2> {
+	if normals::missing(&scene.model) {
+		eprintln!("warning[cem]: some vertices have no normals, generating smooth normals (see --recalculate-normals)");
+		normals::recalculate(&mut scene.model, NormalMode::Smooth);
+	}
+
+	scene
+}
@@ -0,0 +1,288 @@
+use cem::{v2, V2, Scene};
+use cgmath::{InnerSpace, MetricSpace};
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// Structural differences between two scenes, as printed by the `diff` subcommand. Only differences are recorded, so a
+/// diff of identical scenes is empty.
+#[derive(Serialize, Debug)]
+pub struct SceneDiff {
+	pub name: String,
+	pub materials_added: Vec<String>,
+	pub materials_removed: Vec<String>,
+	pub materials_changed: Vec<MaterialDiff>,
+	pub lod_levels: Vec<LodDiff>,
+	pub tag_points_added: Vec<String>,
+	pub tag_points_removed: Vec<String>,
+	/// Frame counts before and after, if they differ.
+	pub frames: Option<(usize, usize)>,
+	/// Frames where a vertex or tag point moved further than the tolerance. Vertices are only compared when both frames
+	/// have the same vertex count, tag points are matched by name.
+	pub deviations: Vec<FrameDeviation>,
+	pub children_added: Vec<String>,
+	pub children_removed: Vec<String>,
+	pub children: Vec<SceneDiff>
+}
+
+/// A material present in both scenes, with the properties that changed as (before, after) pairs.
+#[derive(Serialize, Debug)]
+pub struct MaterialDiff {
+	pub name: String,
+	pub texture: Option<(u32, u32)>,
+	pub texture_name: Option<(String, String)>,
+	pub vertex_count: Option<(u32, u32)>
+}
+
+/// Counts of a level of detail that changed, as (before, after) pairs. A level missing from one side counts as empty.
+#[derive(Serialize, Debug)]
+pub struct LodDiff {
+	pub lod: usize,
+	pub vertices: (usize, usize),
+	pub triangles: (usize, usize)
+}
+
+/// The largest per-vertex and per-tag point differences within a frame.
+#[derive(Serialize, Debug)]
+pub struct FrameDeviation {
+	pub frame: usize,
+	pub position: f32,
+	pub normal: f32,
+	pub texture: f32,
+	pub tag_point: f32
+}
+
+impl SceneDiff {
+	/// Compares two scenes. Materials, tag points, and children are matched by name, in order of appearance.
+	pub fn new(a: &Scene<V2>, b: &Scene<V2>, tolerance: f32) -> Self {
+		let (model_a, model_b) = (&a.model, &b.model);
+
+		let mut diff = SceneDiff {
+			name: a.name.clone(),
+			materials_added: Vec::new(),
+			materials_removed: Vec::new(),
+			materials_changed: Vec::new(),
+			lod_levels: Vec::new(),
+			tag_points_added: Vec::new(),
+			tag_points_removed: Vec::new(),
+			frames: None,
+			deviations: Vec::new(),
+			children_added: Vec::new(),
+			children_removed: Vec::new(),
+			children: Vec::new()
+		};
+
+		// Materials
+
+		let (pairs, removed, added) = match_by_name(&model_a.materials, &model_b.materials, |material| &material.name);
+
+		diff.materials_removed = removed.iter().map(|&index| model_a.materials[index].name.clone()).collect();
+		diff.materials_added = added.iter().map(|&index| model_b.materials[index].name.clone()).collect();
+
+		for (index_a, index_b) in pairs {
+			let (material_a, material_b) = (&model_a.materials[index_a], &model_b.materials[index_b]);
+
+			let material = MaterialDiff {
+				name: material_a.name.clone(),
+				texture: changed(material_a.texture, material_b.texture),
+				texture_name: changed(material_a.texture_name.clone(), material_b.texture_name.clone()),
+				vertex_count: changed(material_a.vertex_count, material_b.vertex_count)
+			};
+
+			if material.texture.is_some() || material.texture_name.is_some() || material.vertex_count.is_some() {
+				diff.materials_changed.push(material);
+			}
+		}
+
+		// Levels of detail
+
+		for lod in 0..model_a.lod_levels.len().max(model_b.lod_levels.len()) {
+			let vertices = (lod_vertices(model_a, lod), lod_vertices(model_b, lod));
+			let triangles = (
+				model_a.lod_levels.get(lod).map(Vec::len).unwrap_or(0),
+				model_b.lod_levels.get(lod).map(Vec::len).unwrap_or(0)
+			);
+
+			if vertices.0 != vertices.1 || triangles.0 != triangles.1 {
+				diff.lod_levels.push(LodDiff { lod, vertices, triangles });
+			}
+		}
+
+		// Tag points
+
+		let (tag_points, removed, added) = match_by_name(&model_a.tag_points, &model_b.tag_points, |name| name);
+
+		diff.tag_points_removed = removed.iter().map(|&index| model_a.tag_points[index].clone()).collect();
+		diff.tag_points_added = added.iter().map(|&index| model_b.tag_points[index].clone()).collect();
+
+		// Frames
+
+		if model_a.frames.len() != model_b.frames.len() {
+			diff.frames = Some((model_a.frames.len(), model_b.frames.len()));
+		}
+
+		for (index, (frame_a, frame_b)) in model_a.frames.iter().zip(model_b.frames.iter()).enumerate() {
+			let mut deviation = FrameDeviation { frame: index, position: 0.0, normal: 0.0, texture: 0.0, tag_point: 0.0 };
+
+			if frame_a.vertices.len() == frame_b.vertices.len() {
+				for (vertex_a, vertex_b) in frame_a.vertices.iter().zip(frame_b.vertices.iter()) {
+					deviation.position = deviation.position.max(vertex_a.position.distance(vertex_b.position));
+					deviation.normal = deviation.normal.max((vertex_a.normal - vertex_b.normal).magnitude());
+					deviation.texture = deviation.texture.max(vertex_a.texture.distance(vertex_b.texture));
+				}
+			}
+
+			for &(index_a, index_b) in &tag_points {
+				if let (Some(&tag_point_a), Some(&tag_point_b)) = (frame_a.tag_points.get(index_a), frame_b.tag_points.get(index_b)) {
+					deviation.tag_point = deviation.tag_point.max(tag_point_a.distance(tag_point_b));
+				}
+			}
+
+			if deviation.position > tolerance || deviation.normal > tolerance || deviation.texture > tolerance || deviation.tag_point > tolerance {
+				diff.deviations.push(deviation);
+			}
+		}
+
+		// Children
+
+		let (pairs, removed, added) = match_by_name(&a.children, &b.children, |child| &child.name);
+
+		diff.children_removed = removed.iter().map(|&index| a.children[index].name.clone()).collect();
+		diff.children_added = added.iter().map(|&index| b.children[index].name.clone()).collect();
+
+		diff.children = pairs.into_iter()
+			.map(|(index_a, index_b)| SceneDiff::new(&a.children[index_a], &b.children[index_b], tolerance))
+			.filter(|child| !child.is_empty())
+			.collect();
+
+		diff
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.materials_added.is_empty() && self.materials_removed.is_empty() && self.materials_changed.is_empty() &&
+			self.lod_levels.is_empty() && self.tag_points_added.is_empty() && self.tag_points_removed.is_empty() &&
+			self.frames.is_none() && self.deviations.is_empty() &&
+			self.children_added.is_empty() && self.children_removed.is_empty() && self.children.is_empty()
+	}
+
+	pub fn write_text<W>(&self, w: &mut W, depth: usize) -> io::Result<()> where W: Write {
+		let indent = "  ".repeat(depth);
+
+		writeln!(w, "{}Scene {:?}", indent, self.name)?;
+
+		for name in &self.materials_removed {
+			writeln!(w, "{}  - material {:?}", indent, name)?;
+		}
+
+		for name in &self.materials_added {
+			writeln!(w, "{}  + material {:?}", indent, name)?;
+		}
+
+		for material in &self.materials_changed {
+			writeln!(w, "{}  ~ material {:?}", indent, material.name)?;
+
+			if let Some((before, after)) = material.texture {
+				writeln!(w, "{}      texture: {} -> {}", indent, before, after)?;
+			}
+
+			if let Some((ref before, ref after)) = material.texture_name {
+				writeln!(w, "{}      texture_name: {:?} -> {:?}", indent, before, after)?;
+			}
+
+			if let Some((before, after)) = material.vertex_count {
+				writeln!(w, "{}      vertex_count: {} -> {} ({:+})", indent, before, after, after as i64 - before as i64)?;
+			}
+		}
+
+		for lod in &self.lod_levels {
+			writeln!(w, "{}  ~ lod {}: vertices {} -> {} ({:+}), triangles {} -> {} ({:+})", indent, lod.lod,
+				lod.vertices.0, lod.vertices.1, lod.vertices.1 as i64 - lod.vertices.0 as i64,
+				lod.triangles.0, lod.triangles.1, lod.triangles.1 as i64 - lod.triangles.0 as i64)?;
+		}
+
+		for name in &self.tag_points_removed {
+			writeln!(w, "{}  - tag point {:?}", indent, name)?;
+		}
+
+		for name in &self.tag_points_added {
+			writeln!(w, "{}  + tag point {:?}", indent, name)?;
+		}
+
+		if let Some((before, after)) = self.frames {
+			writeln!(w, "{}  ~ frames: {} -> {} ({:+})", indent, before, after, after as i64 - before as i64)?;
+		}
+
+		for deviation in &self.deviations {
+			writeln!(w, "{}  ~ frame {}: max deviation position {}, normal {}, texture {}, tag point {}", indent, deviation.frame,
+				deviation.position, deviation.normal, deviation.texture, deviation.tag_point)?;
+		}
+
+		for name in &self.children_removed {
+			writeln!(w, "{}  - child scene {:?}", indent, name)?;
+		}
+
+		for name in &self.children_added {
+			writeln!(w, "{}  + child scene {:?}", indent, name)?;
+		}
+
+		for child in &self.children {
+			child.write_text(w, depth + 1)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// A (before, after) pair, if the value changed.
+fn changed<T>(before: T, after: T) -> Option<(T, T)> where T: PartialEq {
+	if before != after { Some((before, after)) } else { None }
+}
+
+/// Number of distinct vertices referenced by the triangles of a level of detail.
+fn lod_vertices(model: &V2, lod: usize) -> usize {
+	let triangles = match model.lod_levels.get(lod) {
+		Some(triangles) => triangles,
+		None => return 0
+	};
+
+	let mut vertices = HashSet::new();
+
+	for &v2::Material { triangles: ref selections, vertex_offset, .. } in &model.materials {
+		let selection = match selections.get(lod) {
+			Some(selection) => selection,
+			None => continue
+		};
+
+		let start = (selection.offset as usize).min(triangles.len());
+		let end = (selection.offset as usize + selection.len as usize).min(triangles.len());
+
+		for triangle in &triangles[start..end] {
+			vertices.insert(vertex_offset as u64 + triangle.0 as u64);
+			vertices.insert(vertex_offset as u64 + triangle.1 as u64);
+			vertices.insert(vertex_offset as u64 + triangle.2 as u64);
+		}
+	}
+
+	vertices.len()
+}
+
+/// Pairs up items with equal names, in order of appearance. Returns the pairs of indices, followed by the indices of
+/// unpaired items in `a` and in `b`.
+fn match_by_name<T, F>(a: &[T], b: &[T], name: F) -> (Vec<(usize, usize)>, Vec<usize>, Vec<usize>) where F: Fn(&T) -> &String {
+	let mut paired = vec![false; b.len()];
+	let mut pairs = Vec::new();
+	let mut removed = Vec::new();
+
+	for (index_a, item) in a.iter().enumerate() {
+		match (0..b.len()).find(|&index_b| !paired[index_b] && name(&b[index_b]) == name(item)) {
+			Some(index_b) => {
+				paired[index_b] = true;
+				pairs.push((index_a, index_b));
+			},
+			None => removed.push(index_a)
+		}
+	}
+
+	let added = (0..b.len()).filter(|&index| !paired[index]).collect();
+
+	(pairs, removed, added)
+}
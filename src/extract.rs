@@ -0,0 +1,210 @@
+use bounds;
+use cem::{v2, V2, Scene};
+use std::{fmt, io, mem};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A material or child scene chosen by its index, or by its name if it isn't a number.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reference {
+	Index(usize),
+	Name(String)
+}
+
+impl FromStr for Reference {
+	type Err = String;
+
+	fn from_str(reference: &str) -> Result<Self, Self::Err> {
+		Ok(match reference.parse::<usize>() {
+			Ok(index) => Reference::Index(index),
+			Err(_) => Reference::Name(reference.to_owned())
+		})
+	}
+}
+
+/// A range of frames, written as a single frame `3`, a half open range `2..5`, or with either end left out such as
+/// `4..` or `..2`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameRange {
+	pub start: usize,
+	pub end: Option<usize>
+}
+
+impl FrameRange {
+	/// The frames of an animation with `len` frames that fall in the range, or an error if there are none.
+	pub fn resolve(&self, len: usize) -> io::Result<Range<usize>> {
+		let end = self.end.unwrap_or(len);
+
+		if self.start >= end || end > len {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("frame range {} is empty or outside of the {} frames of the model", self, len)));
+		}
+
+		Ok(self.start..end)
+	}
+}
+
+impl fmt::Display for FrameRange {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.end {
+			Some(end) => write!(f, "{}..{}", self.start, end),
+			None => write!(f, "{}..", self.start)
+		}
+	}
+}
+
+impl FromStr for FrameRange {
+	type Err = String;
+
+	fn from_str(range: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("expected a frame such as 3 or a range such as 2..5, 4.., or ..2, got {:?}", range);
+		let number = |number: &str| number.trim().parse::<usize>().map_err(|_| invalid());
+
+		match range.find("..") {
+			Some(split) => {
+				let (start, end) = (&range[..split], &range[split + 2..]);
+
+				Ok(FrameRange {
+					start: if start.trim().is_empty() { 0 } else { number(start)? },
+					end: if end.trim().is_empty() { None } else { Some(number(end)?) }
+				})
+			},
+			None => {
+				let frame = number(range)?;
+				let end = frame.checked_add(1).ok_or_else(|| format!("frame {} is out of range", frame))?;
+
+				Ok(FrameRange { start: frame, end: Some(end) })
+			}
+		}
+	}
+}
+
+/// What to keep of a scene. Empty or missing parts keep everything.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+	/// Path of child scenes to descend into before extracting.
+	pub child: Vec<Reference>,
+	pub materials: Vec<Reference>,
+	pub lod: Option<usize>,
+	pub frames: Option<FrameRange>,
+	/// Whether the child scenes of the extracted scene are kept.
+	pub children: bool
+}
+
+/// Extracts part of a scene as a standalone scene. Vertices that are no longer used by any triangle are removed, and
+/// the triangles, materials, and bounds are updated to match. Kept child scenes are left as they were. The scene must
+/// pass `validate::check`.
+pub fn extract(mut scene: Scene<V2>, selection: &Selection) -> io::Result<Scene<V2>> {
+	for child in &selection.child {
+		let index = match *child {
+			Reference::Index(index) if index < scene.children.len() => index,
+			Reference::Name(ref name) => match scene.children.iter().position(|scene| &scene.name == name) {
+				Some(index) => index,
+				None => return Err(not_found(format!("scene {:?} has no child scene named {:?}", scene.name, name)))
+			},
+			Reference::Index(index) => return Err(not_found(format!("scene {:?} has only {} child scenes, there is no child {}", scene.name, scene.children.len(), index)))
+		};
+
+		scene = scene.children.swap_remove(index);
+	}
+
+	if !selection.children {
+		scene.children.clear();
+	}
+
+	let model = &mut scene.model;
+
+	let mut keep = vec![selection.materials.is_empty(); model.materials.len()];
+
+	for material in &selection.materials {
+		let index = match *material {
+			Reference::Index(index) if index < model.materials.len() => index,
+			Reference::Name(ref name) => match model.materials.iter().position(|material| &material.name == name) {
+				Some(index) => index,
+				None => return Err(not_found(format!("there is no material named {:?}", name)))
+			},
+			Reference::Index(index) => return Err(not_found(format!("the model has only {} materials, there is no material {}", model.materials.len(), index)))
+		};
+
+		keep[index] = true;
+	}
+
+	let lods = match selection.lod {
+		Some(lod) if lod < model.lod_levels.len() => lod..lod + 1,
+		Some(lod) => return Err(not_found(format!("the model has only {} levels of detail, there is no level {}", model.lod_levels.len(), lod))),
+		None => 0..model.lod_levels.len()
+	};
+
+	let frames = match selection.frames {
+		Some(range) => range.resolve(model.frames.len())?,
+		None => 0..model.frames.len()
+	};
+
+	let mut materials = Vec::new();
+	let mut lod_levels = vec![Vec::new(); lods.len()];
+	let mut sources = Vec::new();
+
+	for (material, _) in mem::take(&mut model.materials).into_iter().zip(keep).filter(|&(_, keep)| keep) {
+		let offset = material.vertex_offset as usize;
+
+		// Vertices are renumbered in their original order, skipping the ones that none of the kept triangles use.
+		let mut used = vec![false; material.vertex_count as usize];
+
+		for lod in lods.clone() {
+			let selection = material.triangles[lod];
+
+			for triangle in &model.lod_levels[lod][selection.offset as usize..(selection.offset + selection.len) as usize] {
+				used[triangle.0 as usize] = true;
+				used[triangle.1 as usize] = true;
+				used[triangle.2 as usize] = true;
+			}
+		}
+
+		let first = sources.len();
+		let mut remap = vec![0; used.len()];
+
+		for (index, used) in used.into_iter().enumerate() {
+			if used {
+				remap[index] = (sources.len() - first) as u32;
+				sources.push(offset + index);
+			}
+		}
+
+		let triangles = lods.clone().zip(lod_levels.iter_mut()).map(|(lod, triangles)| {
+			let selection = material.triangles[lod];
+			let start = triangles.len();
+
+			triangles.extend(model.lod_levels[lod][selection.offset as usize..(selection.offset + selection.len) as usize].iter()
+				.map(|triangle| (remap[triangle.0 as usize], remap[triangle.1 as usize], remap[triangle.2 as usize])));
+
+			v2::TriangleSelection { offset: start as u32, len: (triangles.len() - start) as u32 }
+		}).collect();
+
+		materials.push(v2::Material {
+			triangles,
+			vertex_offset: first as u32,
+			vertex_count: (sources.len() - first) as u32,
+			..material
+		});
+	}
+
+	if materials.is_empty() {
+		return Err(not_found("no materials were selected"));
+	}
+
+	model.materials = materials;
+	model.lod_levels = lod_levels;
+	model.frames.truncate(frames.end);
+	model.frames.drain(..frames.start);
+
+	for frame in &mut model.frames {
+		frame.vertices = sources.iter().map(|&source| frame.vertices[source]).collect();
+	}
+
+	bounds::recompute_model(&mut scene.model, &scene.name, false);
+
+	Ok(scene)
+}
+
+fn not_found<M>(message: M) -> io::Error where M: Into<String> {
+	io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
@@ -0,0 +1,221 @@
+use cem::{v2, V2, Scene};
+use cgmath::InnerSpace;
+use extract::FrameRange;
+use std::io;
+
+/// An edit to the sequence of animation frames.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operation {
+	/// Keep only the frames in the range.
+	Keep(FrameRange),
+	/// Play the animation backwards.
+	Reverse,
+	/// Play the animation forwards and then backwards, without repeating the first and last frames, so that it loops
+	/// smoothly.
+	PingPong,
+	/// Drop every Nth frame, starting with frame N - 1.
+	DropEvery(usize),
+	/// Spread the given number of frames evenly over the animation, keeping the first and last frames and linearly
+	/// interpolating the rest.
+	Resample(usize)
+}
+
+/// Applies the operation to the frames of the scene and every child scene, so that they stay in step.
+pub fn apply(scene: &mut Scene<V2>, operation: Operation) -> io::Result<()> {
+	apply_model(&mut scene.model, operation).map_err(|e| io::Error::new(e.kind(), format!("scene {:?}: {}", scene.name, e)))?;
+
+	for child in &mut scene.children {
+		apply(child, operation)?;
+	}
+
+	Ok(())
+}
+
+fn apply_model(model: &mut V2, operation: Operation) -> io::Result<()> {
+	let len = model.frames.len();
+
+	let order = match operation {
+		Operation::Keep(range) => range.resolve(len)?.collect(),
+		Operation::Reverse => (0..len).rev().collect(),
+		Operation::PingPong => (0..len).chain((1..len.saturating_sub(1)).rev()).collect::<Vec<usize>>(),
+		Operation::DropEvery(n) if n >= 2 => (0..len).filter(|index| (index + 1) % n != 0).collect(),
+		Operation::DropEvery(n) => return Err(invalid(format!("dropping every {} frames would leave no frames, use 2 or more", n))),
+		Operation::Resample(count) => return resample(model, count)
+	};
+
+	let frames = order.into_iter().map(|index| copy(&model.frames[index])).collect();
+	model.frames = frames;
+
+	Ok(())
+}
+
+fn resample(model: &mut V2, count: usize) -> io::Result<()> {
+	if count == 0 {
+		return Err(invalid("an animation needs at least one frame"));
+	}
+
+	if model.frames.is_empty() {
+		return Err(invalid("the model has no frames to resample"));
+	}
+
+	let last = model.frames.len() - 1;
+
+	let frames = (0..count).map(|index| {
+		let time = if count == 1 { 0.0 } else { index as f32 * last as f32 / (count - 1) as f32 };
+		let before = (time.floor() as usize).min(last);
+		let (a, b) = (&model.frames[before], &model.frames[(before + 1).min(last)]);
+		let amount = time - before as f32;
+
+		if amount == 0.0 {
+			return copy(a);
+		}
+
+		let vertices = a.vertices.iter().zip(&b.vertices).map(|(a, b)| {
+			let normal = a.normal.lerp(b.normal, amount);
+
+			v2::Vertex {
+				position: a.position + (b.position - a.position) * amount,
+				// Opposite normals cancel out, in which case the nearest frame's normal is the best guess.
+				normal: if normal.magnitude2() > 0.0 { normal.normalize() } else if amount < 0.5 { a.normal } else { b.normal },
+				texture: a.texture + (b.texture - a.texture) * amount
+			}
+		}).collect();
+
+		let tag_points = a.tag_points.iter().zip(&b.tag_points).map(|(&a, &b)| a + (b - a) * amount).collect();
+
+		v2::Frame {
+			transform: if amount < 0.5 { a.transform } else { b.transform },
+			..v2::Frame::from_vertices(vertices, tag_points, model.center)
+		}
+	}).collect();
+
+	model.frames = frames;
+
+	Ok(())
+}
+
+/// Adds the frames of another model with the same triangles as new poses of the root model of the scene, inserting
+/// them before frame `at`, or overwriting the frames from `at` on if `replace` is set. Without an index, the poses are
+/// appended after the last frame. Returns the number of frames added.
+///
+/// The most detailed level of detail of both models must have the same triangles in the same order, taking the
+/// materials in order, though the poses may split them into materials and number their vertices differently. This is
+/// how the OBJ and COLLADA exporters lay out a model, so an exported model can be posed and imported again. Only the
+/// positions and normals are taken from the poses. Tag points with the same name are taken from the poses as well,
+/// the others are copied from the frame that the poses are inserted after.
+pub fn add(scene: &mut Scene<V2>, poses: &Scene<V2>, at: Option<usize>, replace: bool) -> io::Result<usize> {
+	let (model, poses) = (&mut scene.model, &poses.model);
+	let at = at.unwrap_or(model.frames.len());
+
+	if at > model.frames.len() {
+		return Err(invalid(format!("cannot add frames at index {}, the model only has {} frames", at, model.frames.len())));
+	}
+
+	if model.frames.is_empty() {
+		return Err(invalid("the model has no frames to match the poses against"));
+	}
+
+	let remap = match_vertices(model, poses)?;
+
+	let tag_points = model.tag_points.iter().map(|name| poses.tag_points.iter().position(|pose_name| pose_name == name)).collect::<Vec<_>>();
+	let neighbour = &model.frames[at.saturating_sub(1)];
+
+	let frames = poses.frames.iter().map(|pose| {
+		let vertices = neighbour.vertices.iter().zip(&remap).map(|(&vertex, &source)| v2::Vertex {
+			position: pose.vertices[source].position,
+			normal: pose.vertices[source].normal,
+			..vertex
+		}).collect();
+
+		let tag_points = tag_points.iter().zip(&neighbour.tag_points).map(|(&source, &tag_point)| match source {
+			Some(source) => pose.tag_points[source],
+			None => tag_point
+		}).collect();
+
+		v2::Frame {
+			transform: neighbour.transform,
+			..v2::Frame::from_vertices(vertices, tag_points, model.center)
+		}
+	}).collect::<Vec<_>>();
+
+	let count = frames.len();
+	let replaced = if replace { at..(at + count).min(model.frames.len()) } else { at..at };
+
+	model.frames.splice(replaced, frames);
+
+	Ok(count)
+}
+
+/// Finds the vertex of the poses that matches each vertex of the model, by walking the corners of the triangles of
+/// the first level of detail of both.
+fn match_vertices(model: &V2, poses: &V2) -> io::Result<Vec<usize>> {
+	let (model_corners, pose_corners) = (corners(model)?, corners(poses)?);
+
+	if model_corners.len() != pose_corners.len() {
+		return Err(invalid(format!("the poses have {} triangles, but the model has {}", pose_corners.len() / 3, model_corners.len() / 3)));
+	}
+
+	let vertex_count = model.frames[0].vertices.len();
+	let pose_vertex_count = poses.frames.first().map(|frame| frame.vertices.len()).unwrap_or(0);
+	let mut remap = vec![None; vertex_count];
+
+	for (index, (&vertex, &pose_vertex)) in model_corners.iter().zip(&pose_corners).enumerate() {
+		if vertex >= vertex_count || pose_vertex >= pose_vertex_count {
+			return Err(invalid(format!("triangle {} refers to a vertex that does not exist", index / 3)));
+		}
+
+		match remap[vertex] {
+			// The poses may weld vertices that the model keeps apart, but not the other way around.
+			Some(existing) if existing != pose_vertex && !same_pose(poses, existing, pose_vertex) => {
+				return Err(invalid(format!("triangle {} of the poses does not line up with the model, the triangles must be in the same order", index / 3)));
+			},
+			_ => remap[vertex] = Some(pose_vertex)
+		}
+	}
+
+	remap.into_iter().enumerate().map(|(vertex, source)| {
+		source.ok_or_else(|| invalid(format!("vertex {} is not used by the first level of detail, so it cannot be matched with the poses", vertex)))
+	}).collect()
+}
+
+/// The vertex indices of the corners of every triangle in the first level of detail, in material order.
+fn corners(model: &V2) -> io::Result<Vec<usize>> {
+	let triangles = model.lod_levels.first().ok_or_else(|| invalid("a model has no levels of detail"))?;
+	let mut corners = Vec::new();
+
+	for material in &model.materials {
+		let selection = match material.triangles.first() {
+			Some(selection) => selection,
+			None => continue
+		};
+
+		let offset = material.vertex_offset as usize;
+
+		let triangles = triangles.get(selection.offset as usize..(selection.offset + selection.len) as usize)
+			.ok_or_else(|| invalid(format!("material {:?} selects triangles that do not exist", material.name)))?;
+
+		for &(a, b, c) in triangles {
+			corners.extend_from_slice(&[offset + a as usize, offset + b as usize, offset + c as usize]);
+		}
+	}
+
+	Ok(corners)
+}
+
+/// Whether two vertices of the poses have the same position and normal in every frame.
+fn same_pose(poses: &V2, a: usize, b: usize) -> bool {
+	poses.frames.iter().all(|frame| frame.vertices[a].position == frame.vertices[b].position && frame.vertices[a].normal == frame.vertices[b].normal)
+}
+
+fn copy(frame: &v2::Frame) -> v2::Frame {
+	v2::Frame {
+		vertices: frame.vertices.clone(),
+		tag_points: frame.tag_points.clone(),
+		transform: frame.transform,
+		collider: frame.collider
+	}
+}
+
+fn invalid<M>(message: M) -> io::Error where M: Into<String> {
+	io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
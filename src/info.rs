@@ -0,0 +1,170 @@
+use cem::{V2, Scene};
+use convert::read_scene;
+use cgmath::Point3;
+use std::io::{self, Read, Write};
+
+/// Summary of a CEM file, as printed by the `info` subcommand.
+#[derive(Serialize, Debug)]
+pub struct Info {
+	pub version: String,
+	pub scene: SceneInfo
+}
+
+#[derive(Serialize, Debug)]
+pub struct SceneInfo {
+	pub name: String,
+	pub center: [f32; 3],
+	pub materials: Vec<MaterialInfo>,
+	/// Triangle count of each level of detail.
+	pub lod_levels: Vec<usize>,
+	pub tag_points: Vec<String>,
+	pub frames: Vec<FrameInfo>,
+	pub children: Vec<SceneInfo>
+}
+
+#[derive(Serialize, Debug)]
+pub struct MaterialInfo {
+	pub name: String,
+	pub texture: u32,
+	pub texture_name: String,
+	pub vertex_offset: u32,
+	pub vertex_count: u32,
+	/// Triangles of each level of detail used by the material.
+	pub triangles: Vec<SelectionInfo>
+}
+
+#[derive(Serialize, Debug)]
+pub struct SelectionInfo {
+	pub offset: u32,
+	pub len: u32
+}
+
+#[derive(Serialize, Debug)]
+pub struct FrameInfo {
+	pub vertices: usize,
+	/// Bounding box stored in the file.
+	pub bounds: Bounds,
+	/// Bounding sphere radius stored in the file.
+	pub radius: f32,
+	/// Smallest and largest coordinates of the vertices, or None if the frame is empty. These differ from the stored
+	/// bounds if the file was edited without recomputing them.
+	pub vertex_bounds: Option<Bounds>,
+	/// Transform of the frame, as columns.
+	pub transform: [[f32; 4]; 4]
+}
+
+#[derive(Serialize, Debug, Copy, Clone)]
+pub struct Bounds {
+	pub lower: [f32; 3],
+	pub upper: [f32; 3]
+}
+
+impl Info {
+	pub fn read<R>(r: R) -> io::Result<Self> where R: Read {
+		let scene = read_scene(r, "inspect")?;
+
+		Ok(Info {
+			version: "2.0".to_owned(),
+			scene: SceneInfo::new(&scene)
+		})
+	}
+
+	pub fn write_text<W>(&self, mut w: W) -> io::Result<()> where W: Write {
+		writeln!(w, "CEM version {}", self.version)?;
+
+		self.scene.write_text(&mut w, 0)
+	}
+}
+
+impl SceneInfo {
+	fn new(scene: &Scene<V2>) -> Self {
+		let model = &scene.model;
+
+		SceneInfo {
+			name: scene.name.clone(),
+			center: model.center.into(),
+			materials: model.materials.iter().map(|material| MaterialInfo {
+				name: material.name.clone(),
+				texture: material.texture,
+				texture_name: material.texture_name.clone(),
+				vertex_offset: material.vertex_offset,
+				vertex_count: material.vertex_count,
+				triangles: material.triangles.iter().map(|selection| SelectionInfo { offset: selection.offset, len: selection.len }).collect()
+			}).collect(),
+			lod_levels: model.lod_levels.iter().map(|triangles| triangles.len()).collect(),
+			tag_points: model.tag_points.clone(),
+			frames: model.frames.iter().map(|frame| FrameInfo {
+				vertices: frame.vertices.len(),
+				bounds: Bounds { lower: frame.collider.aabb.lower.into(), upper: frame.collider.aabb.upper.into() },
+				radius: frame.collider.radius,
+				vertex_bounds: bounds(frame.vertices.iter().map(|vertex| vertex.position)),
+				transform: frame.transform.into()
+			}).collect(),
+			children: scene.children.iter().map(SceneInfo::new).collect()
+		}
+	}
+
+	fn write_text<W>(&self, w: &mut W, depth: usize) -> io::Result<()> where W: Write {
+		let indent = "  ".repeat(depth);
+
+		writeln!(w, "{}Scene {:?}", indent, self.name)?;
+		writeln!(w, "{}  center: {:?}", indent, self.center)?;
+
+		writeln!(w, "{}  materials: {}", indent, self.materials.len())?;
+		for (index, material) in self.materials.iter().enumerate() {
+			writeln!(w, "{}    [{}] name: {:?}, texture: {}, texture_name: {:?}, vertices: {}..{} ({})",
+				indent, index, material.name, material.texture, material.texture_name,
+				material.vertex_offset, material.vertex_offset as u64 + material.vertex_count as u64, material.vertex_count)?;
+
+			for (lod, selection) in material.triangles.iter().enumerate() {
+				writeln!(w, "{}      lod {}: triangles {}..{} ({})", indent, lod, selection.offset, selection.offset as u64 + selection.len as u64, selection.len)?;
+			}
+		}
+
+		writeln!(w, "{}  lod levels: {}", indent, self.lod_levels.len())?;
+		for (lod, triangles) in self.lod_levels.iter().enumerate() {
+			writeln!(w, "{}    [{}] {} triangles", indent, lod, triangles)?;
+		}
+
+		writeln!(w, "{}  tag points: {}", indent, self.tag_points.len())?;
+		for (index, name) in self.tag_points.iter().enumerate() {
+			writeln!(w, "{}    [{}] {:?}", indent, index, name)?;
+		}
+
+		writeln!(w, "{}  frames: {}", indent, self.frames.len())?;
+		for (index, frame) in self.frames.iter().enumerate() {
+			writeln!(w, "{}    [{}] {} vertices, bounds {:?} to {:?}, radius {}", indent, index, frame.vertices, frame.bounds.lower, frame.bounds.upper, frame.radius)?;
+
+			if let Some(bounds) = frame.vertex_bounds {
+				writeln!(w, "{}      vertices from {:?} to {:?}", indent, bounds.lower, bounds.upper)?;
+			}
+
+			writeln!(w, "{}      transform {:?}", indent, frame.transform)?;
+		}
+
+		writeln!(w, "{}  children: {}", indent, self.children.len())?;
+		for child in &self.children {
+			child.write_text(w, depth + 2)?;
+		}
+
+		Ok(())
+	}
+}
+
+fn bounds<I>(positions: I) -> Option<Bounds> where I: IntoIterator<Item=Point3<f32>> {
+	positions.into_iter().fold(None, |bounds, position| {
+		let position: [f32; 3] = position.into();
+
+		Some(match bounds {
+			Some(mut bounds) => {
+				for (axis, &value) in position.iter().enumerate() {
+					bounds.lower[axis] = f32::min(bounds.lower[axis], value);
+					bounds.upper[axis] = f32::max(bounds.upper[axis], value);
+				}
+
+				bounds
+			},
+			None => Bounds { lower: position, upper: position }
+		})
+	})
+}
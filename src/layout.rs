@@ -0,0 +1,129 @@
+use cem::{V2, Model, MAGIC};
+use std::io;
+
+/// Child scenes may be nested this deep. Readers and writers recurse into children, so this keeps them off the end of
+/// the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Smallest possible size of a scene: the header, quantities, an empty name, and the center.
+const MIN_SCENE: u64 = 8 + 28 + 4 + 12;
+
+/// Walks the layout of a CEMv2 file without decoding it, making sure that every count read from the file fits in the
+/// remaining bytes. The decoder preallocates based on these counts, so a corrupt file could otherwise make it request
+/// gigabytes of memory before finding out that the data isn't there.
+pub fn check(bytes: &[u8]) -> io::Result<()> {
+	Layout { bytes, position: 0 }.scene(0)
+}
+
+struct Layout<'a> {
+	bytes: &'a [u8],
+	position: usize
+}
+
+impl<'a> Layout<'a> {
+	fn remaining(&self) -> u64 {
+		(self.bytes.len() - self.position) as u64
+	}
+
+	fn error(&self, message: String) -> io::Error {
+		io::Error::new(io::ErrorKind::InvalidData, format!("corrupt CEM file at byte {}: {}", self.position, message))
+	}
+
+	fn skip(&mut self, len: u64, what: &str) -> io::Result<()> {
+		if len > self.remaining() {
+			return Err(self.error(format!("{} needs {} bytes, but only {} remain", what, len, self.remaining())));
+		}
+
+		self.position += len as usize;
+
+		Ok(())
+	}
+
+	/// Checks that `count` items of at least `size` bytes each could fit in the rest of the file.
+	fn fits(&self, count: u32, size: u64, what: &str) -> io::Result<()> {
+		match (count as u64).checked_mul(size) {
+			Some(len) if len <= self.remaining() => Ok(()),
+			_ => Err(self.error(format!("{} {} don't fit in the remaining {} bytes", count, what, self.remaining())))
+		}
+	}
+
+	fn u32(&mut self, what: &str) -> io::Result<u32> {
+		let start = self.position;
+		self.skip(4, what)?;
+
+		let bytes = &self.bytes[start..start + 4];
+
+		Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+
+	fn string(&mut self, what: &str) -> io::Result<()> {
+		let len = self.u32(what)?;
+
+		self.skip(len as u64, what)
+	}
+
+	fn scene(&mut self, depth: usize) -> io::Result<()> {
+		if depth > MAX_DEPTH {
+			return Err(self.error(format!("child scenes are nested more than {} levels deep", MAX_DEPTH)));
+		}
+
+		// The root header has already been checked by the caller, but children carry their own.
+		if depth > 0 {
+			let magic = self.u32("header")?;
+			let version = self.u32("header")?;
+			let header = V2::HEADER;
+
+			if magic != MAGIC || version != header.major as u32 | (header.minor as u32) << 16 {
+				return Err(self.error("child scene is not a CEM version 2.0 model".to_owned()));
+			}
+		}
+
+		let _triangles = self.u32("quantities")?;
+		let vertices = self.u32("quantities")?;
+		let tag_points = self.u32("quantities")?;
+		let materials = self.u32("quantities")?;
+		let frames = self.u32("quantities")?;
+		let children = self.u32("quantities")?;
+		let lod_levels = self.u32("quantities")?;
+
+		self.string("scene name")?;
+		self.skip(12, "center")?;
+
+		self.fits(lod_levels, 4, "levels of detail")?;
+
+		for _ in 0..lod_levels {
+			let triangles = self.u32("level of detail")?;
+
+			self.fits(triangles, 12, "triangles")?;
+			self.skip(triangles as u64 * 12, "triangles")?;
+		}
+
+		self.fits(materials, 20 + lod_levels as u64 * 8, "materials")?;
+
+		for _ in 0..materials {
+			self.string("material name")?;
+			self.skip(4 + lod_levels as u64 * 8 + 8, "material")?;
+			self.string("material texture name")?;
+		}
+
+		self.fits(tag_points, 4, "tag points")?;
+
+		for _ in 0..tag_points {
+			self.string("tag point name")?;
+		}
+
+		// Radius, vertices, tag point positions, transform, and bounding box.
+		let frame = 4 + vertices as u64 * 32 + tag_points as u64 * 12 + 64 + 24;
+
+		self.fits(frames, frame, "frames")?;
+		self.skip(frames as u64 * frame, "frames")?;
+
+		self.fits(children, MIN_SCENE, "child scenes")?;
+
+		for _ in 0..children {
+			self.scene(depth + 1)?;
+		}
+
+		Ok(())
+	}
+}
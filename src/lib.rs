@@ -0,0 +1,34 @@
+extern crate cem;
+extern crate chrono;
+extern crate cgmath;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate ron;
+extern crate xml;
+
+pub mod bounds;
+pub mod collada_export;
+pub mod collada_import;
+mod collada_skin;
+pub mod convert;
+pub mod diff;
+pub mod extract;
+pub mod frames;
+pub mod info;
+pub mod materials;
+pub mod merge;
+mod layout;
+pub mod normals;
+pub mod obj_export;
+pub mod obj_import;
+pub mod optimize;
+pub mod tags;
+pub mod text;
+pub mod textures;
+pub mod transform;
+mod triangulate;
+pub mod validate;
+pub mod weld;
+mod xml_writer;
@@ -1,333 +1,747 @@
 extern crate cem;
+extern crate cemconv;
 extern crate cgmath;
-extern crate structopt;
+extern crate glob;
+extern crate rayon;
+extern crate serde_json;
 #[macro_use]
-extern crate structopt_derive;
-extern crate wavefront_obj;
-extern crate collada;
-extern crate xml;
-
-mod collada_export;
-mod collada_import;
-
-use wavefront_obj::obj::{self, Object, Primitive, VTNIndex};
-use collada::document::ColladaDocument;
-use std::fs::File;
-use std::collections::HashMap;
+extern crate structopt;
+
+mod batch;
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
 use std::io::{self, Read, Write};
-use cem::{ModelHeader, v2, V2, Scene, Model, Encode};
-use cgmath::{Point2, Point3, Vector3, Matrix4, Deg, InnerSpace};
+use cem::{V2, Scene};
+use cemconv::{convert, diff, extract, frames, info, materials, merge, tags, validate};
+use cemconv::extract::{FrameRange, Reference};
+use cemconv::collada_import::UpAxis;
+use cemconv::convert::{CemFormat, ColladaFormat, Format, ObjFormat, read_scene};
+use cemconv::normals::NormalMode;
+use cemconv::tags::{FrameSelection, Light};
+use cemconv::textures::TextureDir;
+use cemconv::transform::{self, Axis, Rotation};
+use cgmath::{Matrix4, Point3, Vector3, Zero};
+
+#[derive(StructOpt, Debug)]
+enum Opt {
+	#[structopt(name = "convert", about = "Converts a model, or a directory or glob pattern of models, between CEM, OBJ, COLLADA, and the lossless JSON and RON text forms of CEM")]
+	Convert(ConvertOptions),
+	#[structopt(name = "info", about = "Prints the header, materials, levels of detail, tag points, frames, and child scenes of a CEM file")]
+	Info {
+		#[structopt(long = "json", help = "Print the information as JSON")]
+		json: bool,
+		#[structopt(help = "CEM file to inspect, default is stdin")]
+		input: Option<String>
+	},
+	#[structopt(name = "validate", about = "Checks a CEM file for broken references, inconsistent frames, invalid vertex data, and known engine limits, exiting with an error if any problems are found")]
+	Validate {
+		#[structopt(help = "CEM file to check, default is stdin")]
+		input: Option<String>
+	},
+	#[structopt(name = "merge", about = "Combines several CEM, OBJ, COLLADA, JSON, or RON models into one CEM file, joining their geometry or keeping them as child scenes")]
+	Merge(MergeOptions),
+	#[structopt(name = "extract", about = "Writes part of a CEM file as a new CEM file: a child scene, some of the materials, a level of detail, or a range of frames")]
+	Extract {
+		#[structopt(long = "child", raw(number_of_values = "1"), help = "Child scene to extract, by index or name. Repeat to descend into nested child scenes")]
+		child: Vec<Reference>,
+		#[structopt(short = "m", long = "material", raw(number_of_values = "1"), help = "Material to keep, by index or name. Can be repeated, default is every material")]
+		materials: Vec<Reference>,
+		#[structopt(long = "lod", help = "Level of detail to keep, default is every level")]
+		lod: Option<usize>,
+		#[structopt(long = "frames", help = "Frames to keep, such as 3, 2..5, 4.., or ..2, default is every frame")]
+		frames: Option<FrameRange>,
+		#[structopt(long = "children", help = "Keep the child scenes of the extracted scene")]
+		children: bool,
+		#[structopt(short = "o", long = "output", help = "Output CEM file, default is stdout")]
+		output: Option<String>,
+		#[structopt(help = "CEM file to extract from, default is stdin")]
+		input: Option<String>
+	},
+	#[structopt(name = "frames", about = "Edits the animation frames of a CEM file")]
+	Frames(FramesCommand),
+	#[structopt(name = "tags", about = "Lists and edits the tag points of a CEM file, the attachment points and light emitters of the model")]
+	Tags(TagsCommand),
+	#[structopt(name = "materials", about = "Lists and edits the materials of a CEM file, and remaps the textures of many CEM files at once")]
+	Materials(MaterialsCommand),
+	#[structopt(name = "diff", about = "Compares the structure and vertex data of two CEM files, exiting with an error if they differ")]
+	Diff {
+		#[structopt(long = "json", help = "Print the differences as JSON")]
+		json: bool,
+		#[structopt(short = "t", long = "tolerance", default_value = "0.00001", help = "Largest per-vertex position, normal, or texture coordinate difference, or tag point movement, that is ignored")]
+		tolerance: f32,
+		#[structopt(help = "Original CEM file")]
+		before: String,
+		#[structopt(help = "Changed CEM file")]
+		after: String
+	}
+}
+
+#[derive(StructOpt, Debug)]
+enum FramesCommand {
+	#[structopt(name = "keep", about = "Keeps only a range of frames")]
+	Keep {
+		#[structopt(help = "Frames to keep, such as 3, 2..5, 4.., or ..2")]
+		range: FrameRange,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "reverse", about = "Reverses the order of the frames")]
+	Reverse {
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "ping-pong", about = "Appends the frames in reverse, without repeating the first and last frames, so that the animation loops back and forth")]
+	PingPong {
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "drop-every", about = "Drops every Nth frame, speeding up the animation")]
+	DropEvery {
+		#[structopt(help = "Drop one frame out of every this many, at least 2")]
+		n: usize,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "resample", about = "Resamples the animation to a number of frames, interpolating positions, normals, and tag points linearly between the original frames")]
+	Resample {
+		#[structopt(help = "Number of frames to resample to. The first and last frames are kept as they are")]
+		count: usize,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "add", about = "Adds poses from another model with the same triangles as new frames, such as an OBJ or COLLADA file exported from the model and edited")]
+	Add {
+		#[structopt(long = "at", help = "Index of the frame to insert the poses before, default is after the last frame")]
+		at: Option<usize>,
+		#[structopt(long = "replace", help = "Overwrite the frames starting at the index instead of inserting before it")]
+		replace: bool,
+		#[structopt(flatten)]
+		collada: ColladaOptions,
+		#[structopt(short = "o", long = "output", help = "Output CEM file, default is stdout")]
+		output: Option<String>,
+		#[structopt(help = "CEM file to add the frames to")]
+		base: String,
+		#[structopt(help = "Model with the poses. The format is chosen by its extension, and every frame of it is added")]
+		poses: String
+	}
+}
 
 #[derive(StructOpt, Debug)]
-struct Opt {
-	#[structopt(short = "i", long = "input", help = "Input file to convert, default is stdout")]
+enum TagsCommand {
+	#[structopt(name = "list", about = "Lists the tag points of the root model and their positions")]
+	List {
+		#[structopt(long = "frame", default_value = "0", help = "Frame to list the positions of")]
+		frame: usize,
+		#[structopt(help = "CEM file to inspect, default is stdin")]
+		input: Option<String>
+	},
+	#[structopt(name = "add", about = "Adds a tag point to the root model", raw(setting = "structopt::clap::AppSettings::AllowNegativeNumbers"))]
+	Add {
+		#[structopt(help = "Name of the tag point, see light-name for lights")]
+		name: String,
+		#[structopt(help = "Position on the X axis")]
+		x: f32,
+		#[structopt(help = "Position on the Y axis")]
+		y: f32,
+		#[structopt(help = "Position on the Z axis")]
+		z: f32,
+		#[structopt(long = "frame", default_value = "all", help = "Frame to place the tag point in, or all. The other frames place it at the origin")]
+		frame: FrameSelection,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "rename", about = "Renames a tag point of the root model")]
+	Rename {
+		#[structopt(help = "Current name of the tag point")]
+		name: String,
+		#[structopt(help = "New name of the tag point")]
+		new_name: String,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "remove", about = "Removes a tag point from the root model")]
+	Remove {
+		#[structopt(help = "Name of the tag point")]
+		name: String,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "move", about = "Moves a tag point of the root model", raw(setting = "structopt::clap::AppSettings::AllowNegativeNumbers"))]
+	Move {
+		#[structopt(help = "Name of the tag point")]
+		name: String,
+		#[structopt(help = "Position on the X axis")]
+		x: f32,
+		#[structopt(help = "Position on the Y axis")]
+		y: f32,
+		#[structopt(help = "Position on the Z axis")]
+		z: f32,
+		#[structopt(long = "frame", default_value = "all", help = "Frame to move the tag point in, or all")]
+		frame: FrameSelection,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "light-name", about = "Prints the tag point name that makes the game emit light of a color")]
+	LightName {
+		#[structopt(parse(try_from_str = "tags::parse_color"), help = "Color of the light, such as 255,200,64 or #ffc840")]
+		color: [u32; 3],
+		#[structopt(long = "unknown", default_value = "1,1,1", parse(try_from_str = "parse_light_unknown"), help = "The three numbers after the color, whose meaning is not known")]
+		unknown: [u32; 3]
+	}
+}
+
+#[derive(StructOpt, Debug)]
+enum MaterialsCommand {
+	#[structopt(name = "list", about = "Lists the materials of the root model and their textures")]
+	List {
+		#[structopt(help = "CEM file to inspect, default is stdin")]
+		input: Option<String>
+	},
+	#[structopt(name = "set", about = "Changes the name or texture of a material of the root model")]
+	Set {
+		#[structopt(help = "Material to change, by index or name")]
+		material: Reference,
+		#[structopt(long = "name", help = "New name of the material")]
+		name: Option<String>,
+		#[structopt(long = "texture", help = "New texture number of the material")]
+		texture: Option<u32>,
+		#[structopt(long = "texture-name", help = "New texture file name of the material")]
+		texture_name: Option<String>,
+		#[structopt(flatten)]
+		files: EditFiles
+	},
+	#[structopt(name = "remap", about = "Replaces texture names in CEM files in place, according to a mapping file with lines such as old.tga = new.tga")]
+	Remap {
+		#[structopt(help = "Mapping file from old to new texture names")]
+		mapping: String,
+		#[structopt(raw(required = "true"), help = "CEM files to change. A directory or glob pattern changes every matching file")]
+		inputs: Vec<String>
+	}
+}
+
+/// The input and output of a subcommand that edits a CEM file.
+#[derive(StructOpt, Debug)]
+struct EditFiles {
+	#[structopt(short = "o", long = "output", help = "Output CEM file, default is stdout")]
+	output: Option<String>,
+	#[structopt(help = "CEM file to edit, default is stdin")]
+	input: Option<String>
+}
+
+#[derive(StructOpt, Debug)]
+struct ConvertOptions {
+	#[structopt(short = "i", long = "input", help = "Input file to convert, default is stdin. A directory or glob pattern converts every matching file")]
 	input: Option<String>,
-	#[structopt(short = "g", long = "iformat", help = "Format to use for the input")]
-	input_format: Option<String>,
-	#[structopt(short = "f", long = "format", help = "Format to use as the output")]
-	format: String,
-	#[structopt(short = "n", long = "frame", help = "Frame number in the CEM file to extract")]
-	frame_index: Option<usize>,
-	#[structopt(help = "Output file, default is stdout")]
+	#[structopt(short = "g", long = "iformat", default_value = "cem", raw(possible_values = "FileFormat::NAMES", case_insensitive = "true"), help = "Format to use for the input")]
+	input_format: FileFormat,
+	#[structopt(short = "f", long = "format", raw(possible_values = "FileFormat::NAMES", case_insensitive = "true"), help = "Format to use as the output")]
+	format: FileFormat,
+	#[structopt(short = "v", long = "verbose", help = "Print what the importers read and what the processing passes changed")]
+	verbose: bool,
+	#[structopt(long = "debug-bounds", help = "OBJ and COLLADA: Also write the bounding box and radius of each frame and the model center as line objects")]
+	debug_bounds: bool,
+	#[structopt(long = "texture-dir", help = "OBJ and COLLADA: Find the textures of the model in this directory and copy them next to the output, so that it refers to its own copies. OBJ files always get a material library with this")]
+	texture_dir: Option<PathBuf>,
+	#[structopt(long = "symlink-textures", requires = "texture_dir", help = "OBJ and COLLADA: Link to the textures found with --texture-dir instead of copying them")]
+	symlink_textures: bool,
+	#[structopt(long = "name", default_value = "{stem}.{ext}", help = "File name template for batch conversion, {stem} is replaced with the input file name without its extension and {ext} with the extension of the output format")]
+	name_template: String,
+	#[structopt(flatten)]
+	cem: CemOptions,
+	#[structopt(flatten)]
+	transform: TransformOptions,
+	#[structopt(flatten)]
+	obj: ObjOptions,
+	#[structopt(flatten)]
+	collada: ColladaOptions,
+	#[structopt(help = "Output file, default is stdout. When converting a batch, this is the output directory")]
 	output: Option<String>
 }
 
-enum Format {
-	Cem { version: (u16, u16) },
-	Obj { frame_index: usize },
-	Collada
+#[derive(StructOpt, Debug)]
+struct MergeOptions {
+	#[structopt(long = "children", help = "Keep every input after the first as a child scene of it, instead of joining their geometry")]
+	children: bool,
+	#[structopt(flatten)]
+	collada: ColladaOptions,
+	#[structopt(short = "o", long = "output", help = "Output CEM file, default is stdout")]
+	output: Option<String>,
+	#[structopt(raw(required = "true", min_values = "2"), help = "Models to merge, in order. The format of each is chosen by its extension")]
+	inputs: Vec<String>
 }
 
-impl Format {
-	fn parse(format: &str, frame_index: Option<usize>) -> Option<Self> {
-		let frame_index = frame_index.unwrap_or(0);
+#[derive(StructOpt, Debug)]
+struct CemOptions {
+	#[structopt(long = "cem-version", default_value = "2.0", raw(possible_values = "&[\"2.0\"]"), help = "CEM: Version of the CEM files to write")]
+	version: String,
+	#[structopt(long = "recalculate-normals", help = "CEM: Recalculate normals when writing CEM files: smooth, flat, or a smoothing angle in degrees. Normals missing from OBJ and COLLADA input are always generated")]
+	recalculate_normals: Option<NormalMode>,
+	#[structopt(long = "weld", parse(try_from_str = "parse_epsilon"), help = "CEM: Merge vertices whose position, normal, and texture coordinate are within this distance in every frame, and remove degenerate and duplicate triangles")]
+	weld: Option<f32>,
+	#[structopt(long = "optimize", help = "CEM: Reorder triangles for the vertex cache and vertices for fetch locality, without changing how the model looks")]
+	optimize: bool,
+	#[structopt(long = "recompute-bounds", help = "CEM: Recompute the center of the model and the bounding box and radius of every frame from the vertices")]
+	recompute_bounds: bool
+}
 
-		Some(match format {
-			"cem1.3" => Format::Cem { version: (1 ,3) },
-			"cem2" => Format::Cem { version: (2, 0) },
-			"cem" => Format::Cem { version: (2, 0)},
-			"ssmf" => Format::Cem { version: (2, 0) },
-			"obj" => Format::Obj { frame_index },
-			"collada" => Format::Collada,
-			_ => return None
-		})
+#[derive(StructOpt, Debug)]
+struct TransformOptions {
+	#[structopt(long = "scale", parse(try_from_str = "transform::parse_scale"), help = "CEM: Scale the model by a factor, or by a factor per axis written as x,y,z")]
+	scale: Option<Vector3<f32>>,
+	#[structopt(long = "mirror", raw(possible_values = "Axis::NAMES", case_insensitive = "true", number_of_values = "1"), help = "CEM: Mirror the model along an axis, flipping the winding of the triangles. Can be repeated")]
+	mirror: Vec<Axis>,
+	#[structopt(long = "rotate", raw(number_of_values = "1"), help = "CEM: Rotate the model around an axis by some degrees, such as z:90. Can be repeated, the rotations are applied in order")]
+	rotate: Vec<Rotation>,
+	#[structopt(long = "translate", parse(try_from_str = "transform::parse_vector"), help = "CEM: Move the model by an offset written as x,y,z. This is applied after scaling, mirroring, and rotating")]
+	translate: Option<Vector3<f32>>
+}
+
+impl TransformOptions {
+	/// The combined matrix of the operations, or None if there are none.
+	fn matrix(&self) -> Option<Matrix4<f32>> {
+		if self.scale.is_none() && self.mirror.is_empty() && self.rotate.is_empty() && self.translate.is_none() {
+			return None;
+		}
+
+		Some(transform::matrix(
+			self.scale.unwrap_or_else(|| Vector3::new(1.0, 1.0, 1.0)),
+			&self.mirror,
+			&self.rotate,
+			self.translate.unwrap_or_else(Vector3::zero)
+		))
 	}
 }
 
-fn main() {
-	use structopt::StructOpt;
+#[derive(StructOpt, Debug)]
+struct ObjOptions {
+	#[structopt(short = "n", long = "frame", default_value = "0", help = "OBJ: Frame number in the CEM file to extract")]
+	frame_index: usize,
+	#[structopt(long = "mtl", help = "OBJ: Write a material library next to the output file, referencing the textures of the materials")]
+	mtl: bool
+}
 
-	let opt = Opt::from_args();
+#[derive(StructOpt, Debug)]
+struct ColladaOptions {
+	#[structopt(short = "r", long = "fps", default_value = "30", parse(try_from_str = "parse_frame_rate"), help = "COLLADA: Frame rate used to bake skeletal animations when importing")]
+	frame_rate: f32,
+	#[structopt(long = "up-axis", raw(possible_values = "UpAxis::NAMES", case_insensitive = "true"), help = "COLLADA: Axis pointing up, the default is read from the document on import and Y on export")]
+	up_axis: Option<UpAxis>
+}
+
+fn parse_frame_rate(frame_rate: &str) -> Result<f32, String> {
+	match frame_rate.parse::<f32>() {
+		Ok(frame_rate) if frame_rate.is_finite() && frame_rate > 0.0 => Ok(frame_rate),
+		_ => Err(format!("the frame rate must be a positive number, got {:?}", frame_rate))
+	}
+}
+
+fn parse_light_unknown(numbers: &str) -> Result<[u32; 3], String> {
+	let values = numbers.split(',').map(|value| value.trim().parse::<u32>()).collect::<Result<Vec<u32>, _>>();
+
+	match values.as_ref().map(|values| &values[..]) {
+		Ok(&[i, j, k]) => Ok([i, j, k]),
+		_ => Err(format!("expected three whole numbers such as 1,1,1, got {:?}", numbers))
+	}
+}
 
-	let format = match Format::parse(&opt.format, opt.frame_index) {
-		Some(format) => format,
-		None => {
-			eprintln!("Unrecognized output format {:?}", opt.format);
-			return;
+fn parse_epsilon(epsilon: &str) -> Result<f32, String> {
+	match epsilon.parse::<f32>() {
+		Ok(epsilon) if epsilon.is_finite() && epsilon >= 0.0 => Ok(epsilon),
+		_ => Err(format!("the distance must be zero or a positive number, got {:?}", epsilon))
+	}
+}
+
+/// The file formats that can be chosen on the command line.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum FileFormat {
+	Cem,
+	Obj,
+	Collada,
+	Json,
+	Ron
+}
+
+impl FileFormat {
+	const NAMES: &'static [&'static str] = &["cem", "obj", "collada", "json", "ron", "ssmf", "dae"];
+}
+
+impl FromStr for FileFormat {
+	type Err = String;
+
+	fn from_str(format: &str) -> Result<Self, Self::Err> {
+		match &format.to_ascii_lowercase() as &str {
+			"cem" | "ssmf" => Ok(FileFormat::Cem),
+			"obj" => Ok(FileFormat::Obj),
+			"collada" | "dae" => Ok(FileFormat::Collada),
+			"json" => Ok(FileFormat::Json),
+			"ron" => Ok(FileFormat::Ron),
+			_ => Err(format!("unrecognized format {:?}", format))
 		}
-	};
+	}
+}
 
-	let input_format = match Format::parse(opt.input_format.as_ref().map(|s| s as &str).unwrap_or(""), opt.frame_index) {
-		Some(format) => format,
-		None => Format::Cem { version: (2, 0) }
-	};
+/// The format to read or write as, with the options that apply to it.
+fn resolve_format(format: FileFormat, options: &ConvertOptions, textures: &Option<TextureDir>) -> Format {
+	match format {
+		FileFormat::Cem => Format::Cem(CemFormat {
+			version: if options.cem.version == "1.3" { (1, 3) } else { (2, 0) },
+			normals: options.cem.recalculate_normals,
+			transform: options.transform.matrix(),
+			weld: options.cem.weld,
+			optimize: options.cem.optimize,
+			recompute_bounds: options.cem.recompute_bounds,
+			verbose: options.verbose
+		}),
+		FileFormat::Obj => Format::Obj(ObjFormat { frame_index: options.obj.frame_index, mtl: options.obj.mtl, debug_bounds: options.debug_bounds, textures: textures.clone(), verbose: options.verbose }),
+		FileFormat::Collada => Format::Collada(ColladaFormat { frame_rate: options.collada.frame_rate, up_axis: options.collada.up_axis, debug_bounds: options.debug_bounds, textures: textures.clone(), verbose: options.verbose }),
+		FileFormat::Json => Format::Json,
+		FileFormat::Ron => Format::Ron
+	}
+}
 
-	let stdin = io::stdin();
-	let stdout = io::stdout();
-
-	let result = match (opt.input, opt.output) {
-		(None, None) => convert (
-			stdin.lock(),
-			stdout.lock(),
-			input_format,
-			format
-		),
-		(None, Some(path)) => convert (
-			stdin.lock(),
-			match File::create(&path) {
-				Ok(file) => file,
-				Err(e) => {
-					eprintln!("error: failed to create the output file at {} ({})", path, e);
-					return
-				}
-			},
-			input_format,
-			format
-		),
-		(Some(path), None) => convert (
-			match File::open(&path) {
-				Ok(file) => file,
-				Err(e) => {
-					eprintln!("error: failed to open the input file at {} ({})", path, e);
-					return
-				}
-			},
-			stdout.lock(),
-			input_format,
-			format
-		),
-		(Some(input), Some(output)) => convert (
-			match File::open(&input) {
-				Ok(file) => file,
-				Err(e) => {
-					eprintln!("error: failed open the input file at {} ({})", input, e);
-					return
-				}
-			},
-			match File::create(&output) {
-				Ok(file) => file,
-				Err(e) => {
-					eprintln!("error: failed to create the output file at {} ({})", output, e);
-					return
-				}
-			},
-			input_format,
-			format
-		)
-	};
+/// Opens the texture directory once, so that every file of a batch shares it.
+fn texture_dir(options: &ConvertOptions) -> io::Result<Option<TextureDir>> {
+	options.texture_dir.as_ref().map(|path| TextureDir::open(path.clone(), options.symlink_textures)).transpose()
+}
+
+fn main() {
+	use structopt::StructOpt;
 
-	if let Err(e) = result {
-		eprintln!("error: conversion failed: {}", e);
+	if let Err(e) = run_command(Opt::from_args()) {
+		eprintln!("error: {}", e);
+		process::exit(1);
 	}
 }
 
-fn convert<I, O>(mut i: I, mut o: O, input_format: Format, format: Format) -> io::Result<()> where I: Read, O: Write {
-	match (input_format, format) {
-		(Format::Obj { frame_index: _ }, Format::Cem { version: (2, 0) }) => {
-			let mut buffer = String::new();
-			i.read_to_string(&mut buffer)?;
+/// Converts a single file or a batch of files, as specified by the options of the `convert` subcommand.
+fn run_convert(options: ConvertOptions) -> io::Result<()> {
+	let textures = texture_dir(&options)?;
+	let input_format = resolve_format(options.input_format, &options, &None);
+	let format = resolve_format(options.format, &options, &textures);
 
-			let obj = obj::parse(buffer).map_err(
-				|parse| io::Error::new(io::ErrorKind::InvalidData, format!("Error in OBJ file on line {}: {}", parse.line_number, parse.message))
-			)?;
+	if let Some(ref input) = options.input {
+		if batch::is_batch(input) {
+			let output = match options.output {
+				Some(ref output) => Path::new(output),
+				None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "converting a directory or pattern requires an output directory"))
+			};
 
-			let model = obj_to_cem(&obj.objects);
+			let jobs = batch::collect(input, input_format.input_extensions(), output, &options.name_template, format.extension())
+				.map_err(|e| io::Error::new(e.kind(), format!("failed to find the input files: {}", e)))?;
 
-			Scene::root(model).write(&mut o)
-		},
-		(Format::Cem { version: (2, 0) }, Format::Cem { version: (2, 0) }) => {
-			let header = ModelHeader::read(&mut i)?;
+			batch::check_outputs(&jobs)?;
 
-			if header == V2::HEADER {
-				Scene::<V2>::read_without_header(&mut i)?.write(&mut o)
-			} else {
-				unimplemented!("Cannot rewrite non-CEMv2 files yet.")
+			if jobs.is_empty() {
+				eprintln!("warning: no input files matched {}", input);
+				return Ok(());
 			}
-		},
-		(Format::Cem { version: (_, _) }, Format::Obj { frame_index }) => {
-			let header = ModelHeader::read(&mut i)?;
 
-			if header == V2::HEADER {
-				let scene = Scene::<V2>::read_without_header(&mut i)?;
+			let failed = batch::run(&jobs, |input, output, o| {
+				convert::convert(io::BufReader::new(File::open(input)?), o, Some(output), &input_format, &format)
+			});
 
-				if frame_index >= scene.model.frames.len() {
-					return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Tried to extract frame index {} from a CEM file that only has {} frames", frame_index, scene.model.frames.len())));
-				}
+			return if failed > 0 {
+				Err(io::Error::other(format!("{} files failed to convert", failed)))
+			} else {
+				Ok(())
+			};
+		}
+	}
 
-				let buffer = cem2_to_obj(scene.model, frame_index);
+	let input = open_input(options.input)?;
 
-				o.write_all(buffer.as_bytes())
-			} else {
-				unimplemented!("Cannot convert non-CEMv2 files to OBJ yet.")
-			}
+	let result = match options.output {
+		Some(path) => {
+			let output = File::create(&path).map_err(|e| io::Error::new(e.kind(), format!("failed to create the output file at {} ({})", path, e)))?;
+			let mut output = io::BufWriter::new(output);
+
+			convert::convert(input, &mut output, Some(Path::new(&path)), &input_format, &format).and_then(|_| output.flush())
 		},
-		(Format::Cem { version: (_, _) }, Format::Collada) => {
-			let header = ModelHeader::read(&mut i)?;
+		None => convert::convert(input, io::stdout().lock(), None, &input_format, &format)
+	};
 
-			if header == V2::HEADER {
-				let scene = Scene::<V2>::read_without_header(&mut i)?;
+	result.map_err(|e| io::Error::new(e.kind(), format!("conversion failed: {}", e)))
+}
 
-				let buffer = collada_export::convert(scene);
+/// Reads and checks each input of the `merge` subcommand, then writes the merged scene.
+fn run_merge(options: MergeOptions) -> io::Result<()> {
+	let mut scenes = Vec::with_capacity(options.inputs.len());
 
-				o.write_all(buffer.as_bytes())
-			} else {
-				unimplemented!("Cannot convert non-CEMv2 files to COLLADA yet.")
+	for input in &options.inputs {
+		scenes.push(read_any(input, &options.collada)?);
+	}
+
+	let mode = if options.children { merge::Mode::Children } else { merge::Mode::Concatenate };
+
+	write_cem(merge::merge(scenes, mode)?, options.output)
+}
+
+/// Edits the frames of a CEM file, as specified by the `frames` subcommand.
+fn run_frames(command: FramesCommand) -> io::Result<()> {
+	let (operation, files) = match command {
+		FramesCommand::Keep { range, files } => (frames::Operation::Keep(range), files),
+		FramesCommand::Reverse { files } => (frames::Operation::Reverse, files),
+		FramesCommand::PingPong { files } => (frames::Operation::PingPong, files),
+		FramesCommand::DropEvery { n, files } => (frames::Operation::DropEvery(n), files),
+		FramesCommand::Resample { count, files } => (frames::Operation::Resample(count), files),
+		FramesCommand::Add { at, replace, collada, output, base, poses } => {
+			let mut scene = read_any(&base, &collada)?;
+			let poses = read_any(&poses, &collada)?;
+
+			let count = frames::add(&mut scene, &poses, at, replace)?;
+			eprintln!("{} {} frames, the model now has {}", if replace { "replaced" } else { "added" }, count, scene.model.frames.len());
+
+			return write_cem(scene, output);
+		}
+	};
+
+	edit_cem(files, |scene| frames::apply(scene, operation))
+}
+
+/// Lists or edits the tag points of a CEM file, as specified by the `tags` subcommand.
+fn run_tags(command: TagsCommand) -> io::Result<()> {
+	match command {
+		TagsCommand::List { frame, input } => {
+			let scene = read_scene(open_input(input)?, "list the tag points of")?;
+			validate::check(&scene)?;
+
+			let model = &scene.model;
+			let positions = &model.frames.get(frame)
+				.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("the model has only {} frames, there is no frame {}", model.frames.len(), frame)))?
+				.tag_points;
+
+			let stdout = io::stdout();
+			let mut stdout = stdout.lock();
+
+			for (index, (name, position)) in model.tag_points.iter().zip(positions).enumerate() {
+				write!(stdout, "{}: {:?} at {}, {}, {}", index, name, position.x, position.y, position.z)?;
+
+				match name.parse::<Light>() {
+					Ok(light) => writeln!(stdout, ", light of color {}, {}, {}", light.color[0], light.color[1], light.color[2])?,
+					Err(_) => writeln!(stdout)?
+				}
 			}
-		},
-		(Format::Collada, Format::Cem { version: (2, 0) }) => {
-			let mut buffer = String::new();
-			i.read_to_string(&mut buffer)?;
 
-			let xml = buffer.parse::<xml::Element>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}", e)))?;
-			let model = collada_import::convert(ColladaDocument { root_element: xml });
+			Ok(())
+		},
+		TagsCommand::LightName { color, unknown } => {
+			println!("{}", Light { color, unknown });
 
-			Scene::root(model).write(&mut o)
+			Ok(())
 		},
-		_ => unimplemented!()
+		TagsCommand::Add { name, x, y, z, frame, files } => edit_cem(files, |scene| tags::add(&mut scene.model, &name, Point3::new(x, y, z), frame)),
+		TagsCommand::Rename { name, new_name, files } => edit_cem(files, |scene| tags::rename(&mut scene.model, &name, &new_name)),
+		TagsCommand::Remove { name, files } => edit_cem(files, |scene| tags::remove(&mut scene.model, &name)),
+		TagsCommand::Move { name, x, y, z, frame, files } => edit_cem(files, |scene| tags::move_to(&mut scene.model, &name, Point3::new(x, y, z), frame))
 	}
 }
 
-fn obj_to_cem(i: &[Object]) -> V2 {
-	let mut triangles = Vec::new();
-	let mut vertices = Vec::new();
+/// Lists or edits the materials of CEM files, as specified by the `materials` subcommand.
+fn run_materials(command: MaterialsCommand) -> io::Result<()> {
+	match command {
+		MaterialsCommand::List { input } => {
+			let scene = read_scene(open_input(input)?, "list the materials of")?;
 
-	let transformation = Matrix4::from_angle_x(Deg(90.0));
+			let stdout = io::stdout();
+			let mut stdout = stdout.lock();
 
-	{
-		let mut vertex_associations = HashMap::new();
+			for (index, material) in scene.model.materials.iter().enumerate() {
+				writeln!(stdout, "{}: {:?}, texture: {}, texture_name: {:?}", index, material.name, material.texture, material.texture_name)?;
+			}
 
-		let mut resolve_index = |i: &Object, idx: usize, v: VTNIndex| {
-			*vertex_associations.entry((idx, v)).or_insert_with(|| {
-				let index = vertices.len();
+			Ok(())
+		},
+		MaterialsCommand::Set { material, name, texture, texture_name, files } => {
+			let changes = materials::Changes { name, texture, texture_name };
 
-				let position = i.vertices[v.0];
-				let texture = v.1.map(|index| i.tex_vertices[index]).unwrap_or(obj::TVertex { u: 0.0, v: 0.0, w: 0.0 });
-				let normal = v.2.map(|index| i.normals[index]).unwrap_or(obj::Vertex { x: 1.0, y: 0.0, z: 0.0 });
+			if changes.is_empty() {
+				return Err(io::Error::new(io::ErrorKind::InvalidInput, "nothing to change, use --name, --texture, or --texture-name"));
+			}
 
-				let normal = Vector3 { x: normal.x as f32, y: normal.y as f32, z: normal.z as f32 };
-				let position = Point3 { x: position.x as f32, y: position.y as f32, z: position.z as f32 };
+			edit_cem(files, |scene| materials::set(&mut scene.model, &material, &changes))
+		},
+		MaterialsCommand::Remap { mapping, inputs } => {
+			let text = fs::read_to_string(&mapping).map_err(|e| io::Error::new(e.kind(), format!("failed to read the mapping file at {} ({})", mapping, e)))?;
+			let mapping = materials::Mapping::parse(&text).map_err(|e| io::Error::new(e.kind(), format!("invalid mapping file: {}", e)))?;
 
-				let normal = (transformation * normal.normalize().extend(0.0)).truncate();
-				let position = Point3::from_homogeneous(transformation * position.to_homogeneous());
+			let mut paths = Vec::new();
 
-				vertices.push(v2::Vertex {
-					position,
-					normal,
-					texture: Point2 { x: texture.u as f32, y: 1.0 - texture.v as f32 },
-				});
+			for input in &inputs {
+				if batch::is_batch(input) {
+					let inputs = batch::inputs(input, Format::cem().input_extensions())
+						.map_err(|e| io::Error::new(e.kind(), format!("failed to find the input files: {}", e)))?;
 
-				index
-			})
-		};
+					paths.extend(inputs);
+				} else {
+					paths.push(PathBuf::from(input));
+				}
+			}
 
-		for (idx, i) in i.iter().enumerate() {
-			for geometry in &i.geometry {
-				for primitive in geometry.shapes.iter().map(|shape| shape.primitive) {
-					match primitive {
-						Primitive::Triangle(v0, v1, v2) => {
-							triangles.push((
-								resolve_index(i, idx, v0) as u32,
-								resolve_index(i, idx, v1) as u32,
-								resolve_index(i, idx, v2) as u32
-							));
-						},
-						_ => () // Skip lines and points, not supported.
+			let (mut changed_files, mut failed) = (0, 0);
+
+			for path in &paths {
+				match remap_file(path, &mapping) {
+					Ok(0) => (),
+					Ok(changed) => {
+						eprintln!("{}: remapped {} materials", path.display(), changed);
+						changed_files += 1;
+					},
+					Err(e) => {
+						eprintln!("error: failed to remap {}: {}", path.display(), e);
+						failed += 1;
 					}
 				}
 			}
-		}
-	}
 
-	// It appears that there is some limit on the vertex count. This needs to be investigated further, but it appears that
-	// adding more than 2442 instantly crashes the game on model load.
-	if vertices.len() > 2442 {
-		eprintln!("warning[cem]: Vertex count exceeds 2442, this will most likely crash the game. You have been warned.");
-		eprintln!("warning[cem]: {} vertices, {} triangles", vertices.len(), triangles.len());
+			eprintln!("changed {} of {} files, {} failed", changed_files, paths.len(), failed);
+
+			if failed > 0 {
+				Err(io::Error::other(format!("{} files failed to remap", failed)))
+			} else {
+				Ok(())
+			}
+		}
 	}
+}
 
-	// Create the model
+/// Remaps the textures of a CEM file in place, returning the number of materials changed. The file is only written
+/// if something changed, and only once the whole file has been encoded.
+fn remap_file(path: &Path, mapping: &materials::Mapping) -> io::Result<usize> {
+	let mut scene = read_scene(io::BufReader::new(File::open(path)?), "remap")?;
+	let changed = materials::remap_textures(&mut scene, mapping);
 
-	let mut center_builder = ::cem::collider::CenterBuilder::begin();
+	if changed > 0 {
+		let mut encoded = Vec::new();
+		scene.write(&mut encoded)?;
 
-	for vertex in &vertices {
-		center_builder.update(vertex.position);
+		fs::write(path, encoded)?;
 	}
 
-	let center = center_builder.build();
-
-	V2 {
-		center,
-		materials: vec![v2::Material {
-			name: "".to_string(),
-			texture: 0,
-			triangles: vec![
-				v2::TriangleSelection {
-					offset: 0,
-					len: triangles.len() as u32
-				}
-			],
-			vertex_offset: 0,
-			vertex_count: vertices.len() as u32,
-			texture_name: "".to_string()
-		}],
-		lod_levels: vec![
-			triangles
-		],
-		tag_points: vec![],
-		frames: vec![
-			v2::Frame::from_vertices(vertices, vec![], center)
-		]
-	}
+	Ok(changed)
 }
 
-fn cem2_to_obj(cem: V2, frame_index: usize) -> String {
-	use std::fmt::Write;
+/// Reads and checks a CEM file, edits it, and writes the result.
+fn edit_cem<F>(files: EditFiles, edit: F) -> io::Result<()> where F: FnOnce(&mut Scene<V2>) -> io::Result<()> {
+	let mut scene = read_scene(open_input(files.input)?, "edit")?;
+	validate::check(&scene)?;
 
-	let triangle_data = &cem.lod_levels[0];
-	let frame = &cem.frames[frame_index];
+	edit(&mut scene)?;
 
-	let mut string = String::new();
+	write_cem(scene, files.output)
+}
 
-	let transformation = Matrix4::from_angle_x(Deg(-90.0));
+/// Reads and checks a model in any format, chosen by the extension of the file.
+fn read_any(input: &str, collada: &ColladaOptions) -> io::Result<Scene<V2>> {
+	let path = Path::new(input);
+	let extension = path.extension().map(|extension| extension.to_string_lossy().into_owned()).unwrap_or_default();
+
+	let format = match extension.parse::<FileFormat>() {
+		Ok(FileFormat::Cem) => Format::cem(),
+		Ok(FileFormat::Obj) => Format::obj(),
+		Ok(FileFormat::Collada) => Format::Collada(ColladaFormat { frame_rate: collada.frame_rate, up_axis: collada.up_axis, ..ColladaFormat::default() }),
+		Ok(FileFormat::Json) => Format::Json,
+		Ok(FileFormat::Ron) => Format::Ron,
+		Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot tell the format of {} from its extension", input)))
+	};
+
+	open_input(Some(input.to_owned()))
+		.and_then(|i| convert::read(i, &format))
+		.and_then(|scene| validate::check(&scene).map(|()| scene))
+		.map_err(|e| io::Error::new(e.kind(), format!("failed to read {}: {}", input, e)))
+}
 
-	for &v2::Vertex { position, normal, texture } in frame.vertices.iter() {
+/// Writes the result of a subcommand that edits CEM files, to stdout if there is no output file.
+fn write_cem(scene: Scene<V2>, output: Option<String>) -> io::Result<()> {
+	match output {
+		Some(path) => {
+			let output = File::create(&path).map_err(|e| io::Error::new(e.kind(), format!("failed to create the output file at {} ({})", path, e)))?;
+			let mut output = io::BufWriter::new(output);
 
-		let normal = (transformation * normal.normalize().extend(0.0)).truncate();
-		let position = Point3::from_homogeneous(transformation * position.to_homogeneous());
+			convert::write(scene, &mut output, Some(Path::new(&path)), &Format::cem())?;
+			output.flush()
+		},
+		None => convert::write(scene, io::stdout().lock(), None, &Format::cem())
+	}
+}
 
-		writeln!(string, "v {} {} {}", position.x, position.y, position.z).unwrap();
-		writeln!(string, "vn {} {} {}", normal.x, normal.y, normal.z).unwrap();
-		writeln!(string, "vt {} {}", texture.x, 1.0 - texture.y).unwrap();
+/// Opens the input file of a subcommand, or stdin if there is none.
+fn open_input(path: Option<String>) -> io::Result<Box<dyn Read>> {
+	match path {
+		Some(path) => match File::open(&path) {
+			Ok(file) => Ok(Box::new(io::BufReader::new(file))),
+			Err(e) => Err(io::Error::new(e.kind(), format!("failed to open the input file at {} ({})", path, e)))
+		},
+		None => Ok(Box::new(io::stdin()))
 	}
+}
 
-	for &v2::Material { ref name, texture, ref triangles, vertex_offset, vertex_count: _vertex_count, ref texture_name } in &cem.materials {
-		let triangle_slice = triangles[0];
+fn run_command(opt: Opt) -> io::Result<()> {
+	match opt {
+		Opt::Convert(options) => run_convert(options),
+		Opt::Merge(options) => run_merge(options),
+		Opt::Extract { child, materials, lod, frames, children, output, input } => {
+			let scene = read_scene(open_input(input)?, "extract from")?;
+			validate::check(&scene)?;
 
-		writeln!(string, "# name: {}, texture: {}, texture_name: {}", name, texture, texture_name).unwrap();
+			let selection = extract::Selection { child, materials, lod, frames, children };
 
-		for index in 0..triangle_slice.len {
-			let index = index + triangle_slice.offset;
-			let triangle = &triangle_data[index as usize];
+			write_cem(extract::extract(scene, &selection)?, output)
+		},
+		Opt::Frames(command) => run_frames(command),
+		Opt::Tags(command) => run_tags(command),
+		Opt::Materials(command) => run_materials(command),
+		Opt::Info { json, input } => {
+			let info = info::Info::read(open_input(input)?)?;
+
+			let stdout = io::stdout();
+			let mut stdout = stdout.lock();
+
+			if json {
+				serde_json::to_writer_pretty(&mut stdout, &info)?;
+				writeln!(stdout)
+			} else {
+				info.write_text(stdout)
+			}
+		},
+		Opt::Validate { input } => {
+			let scene = read_scene(open_input(input)?, "validate")?;
+			let problems = validate::validate(&scene);
 
-			let indices = (
-				vertex_offset + triangle.0 + 1,
-				vertex_offset + triangle.1 + 1,
-				vertex_offset + triangle.2 + 1
-			);
+			for problem in &problems {
+				println!("{}", problem);
+			}
+
+			if problems.is_empty() {
+				println!("no problems found");
+				Ok(())
+			} else {
+				Err(io::Error::new(io::ErrorKind::InvalidData, format!("found {} problems", problems.len())))
+			}
+		},
+		Opt::Diff { json, tolerance, before, after } => {
+			let before = read_scene(open_input(Some(before))?, "diff")?;
+			let after = read_scene(open_input(Some(after))?, "diff")?;
 
-			writeln!(string, "f {}/{}/{} {}/{}/{} {}/{}/{}", indices.0, indices.0, indices.0, indices.1, indices.1, indices.1, indices.2, indices.2, indices.2).unwrap();
+			let diff = diff::SceneDiff::new(&before, &after, tolerance);
+
+			let stdout = io::stdout();
+			let mut stdout = stdout.lock();
+
+			if json {
+				serde_json::to_writer_pretty(&mut stdout, &diff)?;
+				writeln!(stdout)?;
+			} else if !diff.is_empty() {
+				diff.write_text(&mut stdout, 0)?;
+			}
+
+			if diff.is_empty() {
+				Ok(())
+			} else {
+				Err(io::Error::other("the files differ"))
+			}
 		}
 	}
-
-	string
-}
\ No newline at end of file
+}
@@ -0,0 +1,108 @@
+use cem::{V2, Scene};
+use extract::Reference;
+use std::collections::HashMap;
+use std::io;
+
+/// New values for the fields of a material. Fields that are None are left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+	pub name: Option<String>,
+	pub texture: Option<u32>,
+	pub texture_name: Option<String>
+}
+
+impl Changes {
+	pub fn is_empty(&self) -> bool {
+		self.name.is_none() && self.texture.is_none() && self.texture_name.is_none()
+	}
+}
+
+/// Changes a material of the model, chosen by index or by its current name.
+pub fn set(model: &mut V2, material: &Reference, changes: &Changes) -> io::Result<()> {
+	let count = model.materials.len();
+
+	let material = match *material {
+		Reference::Index(index) => model.materials.get_mut(index)
+			.ok_or_else(|| invalid(format!("the model has only {} materials, there is no material {}", count, index)))?,
+		Reference::Name(ref name) => model.materials.iter_mut().find(|material| &material.name == name)
+			.ok_or_else(|| invalid(format!("there is no material named {:?}", name)))?
+	};
+
+	if let Some(ref name) = changes.name {
+		material.name = name.clone();
+	}
+
+	if let Some(texture) = changes.texture {
+		material.texture = texture;
+	}
+
+	if let Some(ref texture_name) = changes.texture_name {
+		material.texture_name = texture_name.clone();
+	}
+
+	Ok(())
+}
+
+/// Replacements for texture names, read from lines written as `old = new`. Blank lines and lines starting with `#`
+/// are skipped. Old names are matched ignoring ASCII case, as game data folders mix upper and lower case.
+#[derive(Debug, Clone, Default)]
+pub struct Mapping {
+	textures: HashMap<String, String>
+}
+
+impl Mapping {
+	pub fn parse(text: &str) -> io::Result<Self> {
+		let mut textures = HashMap::new();
+
+		for (index, line) in text.lines().enumerate() {
+			let line = line.trim();
+
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut parts = line.splitn(2, '=').map(str::trim);
+
+			match (parts.next(), parts.next()) {
+				(Some(old), Some(new)) if !old.is_empty() => {
+					if textures.insert(old.to_ascii_lowercase(), new.to_owned()).is_some() {
+						return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: texture {:?} is mapped more than once", index + 1, old)));
+					}
+				},
+				_ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: expected a mapping such as old.tga = new.tga, got {:?}", index + 1, line)))
+			}
+		}
+
+		Ok(Mapping { textures })
+	}
+
+	/// The replacement for a texture name, if it is mapped.
+	pub fn get(&self, texture_name: &str) -> Option<&str> {
+		self.textures.get(&texture_name.to_ascii_lowercase()).map(|name| name as &str)
+	}
+}
+
+/// Replaces the texture names of every material in the scene and its children according to the mapping. Returns the
+/// number of materials changed.
+pub fn remap_textures(scene: &mut Scene<V2>, mapping: &Mapping) -> usize {
+	let mut changed = 0;
+
+	for material in &mut scene.model.materials {
+		if let Some(new) = mapping.get(&material.texture_name) {
+			if material.texture_name != new {
+				material.texture_name = new.to_owned();
+				changed += 1;
+			}
+		}
+	}
+
+	for child in &mut scene.children {
+		changed += remap_textures(child, mapping);
+	}
+
+	changed
+}
+
+fn invalid<M>(message: M) -> io::Error where M: Into<String> {
+	io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
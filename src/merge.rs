@@ -0,0 +1,119 @@
+use bounds;
+use cem::{v2, V2, Scene};
+use std::collections::HashSet;
+use std::io;
+
+/// How the inputs of a merge are combined.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+	/// Join the geometry of every input into a single model.
+	Concatenate,
+	/// Keep the first input as the root model, and attach every other input as a child scene of it.
+	Children
+}
+
+/// Combines several scenes into one, named after the first. The center and bounds of the root model are recomputed,
+/// child scenes keep theirs. Every scene must pass `validate::check`.
+pub fn merge(scenes: Vec<Scene<V2>>, mode: Mode) -> io::Result<Scene<V2>> {
+	let mut scenes = scenes.into_iter();
+
+	let mut merged = match scenes.next() {
+		Some(first) => first,
+		None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "there are no models to merge"))
+	};
+
+	for scene in scenes {
+		match mode {
+			Mode::Concatenate => {
+				append(&mut merged.model, &scene.model);
+				merged.children.extend(scene.children);
+			},
+			Mode::Children => merged.children.push(scene)
+		}
+	}
+
+	bounds::recompute_model(&mut merged.model, &merged.name, false);
+
+	Ok(merged)
+}
+
+/// Appends the geometry of a model to another. Materials are rebased onto the joined vertices and triangles, a model
+/// with fewer levels of detail uses its least detailed level for the rest, and a model with fewer frames holds its
+/// last frame until the longer animation is over. Frames keep their own transform, as there is only one per frame.
+fn append(model: &mut V2, other: &V2) {
+	let vertex_base = model.frames.first().map(|frame| frame.vertices.len()).unwrap_or(0) as u32;
+	let lod_count = model.lod_levels.len().max(other.lod_levels.len());
+
+	// Extend both models to the same number of levels of detail first.
+	for lod in model.lod_levels.len()..lod_count {
+		let last = model.lod_levels[lod - 1].clone();
+		model.lod_levels.push(last);
+
+		for material in &mut model.materials {
+			let last = material.triangles[lod - 1];
+			material.triangles.push(last);
+		}
+	}
+
+	let triangle_bases = model.lod_levels.iter().map(|triangles| triangles.len() as u32).collect::<Vec<u32>>();
+
+	for lod in 0..lod_count {
+		let source = &other.lod_levels[lod.min(other.lod_levels.len() - 1)];
+		model.lod_levels[lod].extend_from_slice(source);
+	}
+
+	for material in &other.materials {
+		let last = material.triangles.len() - 1;
+
+		model.materials.push(v2::Material {
+			name: material.name.clone(),
+			texture: material.texture,
+			triangles: (0..lod_count).map(|lod| {
+				let selection = material.triangles[lod.min(last)];
+
+				v2::TriangleSelection { offset: triangle_bases[lod] + selection.offset, len: selection.len }
+			}).collect(),
+			vertex_offset: vertex_base + material.vertex_offset,
+			vertex_count: material.vertex_count,
+			texture_name: material.texture_name.clone()
+		});
+	}
+
+	let names = model.tag_points.iter().cloned().collect::<HashSet<String>>();
+
+	for name in &other.tag_points {
+		if names.contains(name) {
+			eprintln!("warning[cem]: more than one model has a tag point named {:?}", name);
+		}
+	}
+
+	model.tag_points.extend(other.tag_points.iter().cloned());
+
+	for index in model.frames.len()..other.frames.len() {
+		let last = &model.frames[index - 1];
+
+		let copy = v2::Frame {
+			vertices: last.vertices.clone(),
+			tag_points: last.tag_points.clone(),
+			transform: last.transform,
+			collider: last.collider
+		};
+
+		model.frames.push(copy);
+	}
+
+	let mut transforms_differ = false;
+
+	for (index, frame) in model.frames.iter_mut().enumerate() {
+		let source = &other.frames[index.min(other.frames.len() - 1)];
+
+		transforms_differ |= frame.transform != source.transform;
+
+		frame.vertices.extend_from_slice(&source.vertices);
+		frame.tag_points.extend_from_slice(&source.tag_points);
+	}
+
+	if transforms_differ {
+		eprintln!("warning[cem]: the frames of the models being joined have different transforms, keeping those of the first");
+	}
+}
@@ -0,0 +1,215 @@
+use cem::{v2, V2};
+use cgmath::{Point3, Vector3, InnerSpace, Zero};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+/// How vertex normals are generated when recalculating them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NormalMode {
+	/// Every corner at a position shares a single normal.
+	Smooth,
+	/// Each face gets its own normal, only shared between coplanar faces.
+	Flat,
+	/// Faces meeting at an angle of at most this many degrees are smoothed together, sharper edges stay hard.
+	Angle(f32)
+}
+
+impl NormalMode {
+	fn threshold(&self) -> f32 {
+		match *self {
+			NormalMode::Smooth => 180.0,
+			NormalMode::Flat => 0.0,
+			NormalMode::Angle(degrees) => degrees
+		}
+	}
+}
+
+impl FromStr for NormalMode {
+	type Err = String;
+
+	fn from_str(mode: &str) -> Result<Self, Self::Err> {
+		match mode {
+			"smooth" => Ok(NormalMode::Smooth),
+			"flat" => Ok(NormalMode::Flat),
+			angle => match angle.parse::<f32>() {
+				Ok(degrees) if (0.0..=180.0).contains(&degrees) => Ok(NormalMode::Angle(degrees)),
+				_ => Err(format!("expected smooth, flat, or a smoothing angle between 0 and 180 degrees, got {:?}", mode))
+			}
+		}
+	}
+}
+
+/// Whether any vertex of the model lacks a normal. Importers leave the normal zeroed when the source has none.
+pub fn missing(model: &V2) -> bool {
+	model.frames.iter().flat_map(|frame| frame.vertices.iter()).any(|vertex| vertex.normal.is_zero())
+}
+
+/// A vertex of the recalculated model: the vertex it was copied from, the first vertex of its material, and the face
+/// corners whose normals are averaged.
+struct Split {
+	source: usize,
+	base: usize,
+	corners: Vec<FaceCorner>
+}
+
+/// A corner of a triangle, identified by the level of detail, the index of the triangle in it, and the corner (0-2).
+type FaceCorner = (usize, usize, usize);
+
+/// Recalculates the normals of every frame, splitting vertices where the mode requires hard edges.
+///
+/// The split is decided from the first frame so that all frames keep the same topology, but the normals are computed
+/// from the positions of each frame. Corners of lower levels of detail reuse the vertices of the first level where
+/// possible, picking the variant that best matches the face.
+pub fn recalculate(model: &mut V2, mode: NormalMode) {
+	if model.frames.is_empty() {
+		return;
+	}
+
+	let min_dot = (mode.threshold() * PI / 180.0).cos() - 1.0e-4;
+
+	let mut splits: Vec<Split> = Vec::new();
+	let mut materials = Vec::with_capacity(model.materials.len());
+	let mut lod_levels = model.lod_levels.clone();
+
+	for material in &model.materials {
+		let vertex_offset = material.vertex_offset as usize;
+		let first_split = splits.len();
+
+		let positions = &model.frames[0].vertices[vertex_offset..vertex_offset + material.vertex_count as usize];
+
+		// Corners at bitwise identical positions are treated as the same point, even across texture seams.
+		let mut groups = HashMap::new();
+		let group = positions.iter()
+			.map(|vertex| {
+				let len = groups.len();
+				*groups.entry([vertex.position.x.to_bits(), vertex.position.y.to_bits(), vertex.position.z.to_bits()]).or_insert(len)
+			})
+			.collect::<Vec<usize>>();
+
+		let face_normal = |lod: usize, face: usize| {
+			let triangle = model.lod_levels[lod][face];
+			let normal = weighted_normal(positions[triangle.0 as usize].position, positions[triangle.1 as usize].position, positions[triangle.2 as usize].position);
+
+			if normal.magnitude2() > 0.0 { normal.normalize() } else { normal }
+		};
+
+		// Keyed by source vertex and the corners that are smoothed together, yielding an index into splits.
+		let mut keys: HashMap<(usize, Vec<FaceCorner>), usize> = HashMap::new();
+		let mut first_lod: HashMap<usize, Vec<usize>> = HashMap::new();
+
+		for (lod, selection) in material.triangles.iter().enumerate() {
+			let range = selection.offset as usize..(selection.offset + selection.len) as usize;
+
+			let mut faces_at: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+
+			for face in range.clone() {
+				let triangle = model.lod_levels[lod][face];
+
+				for (corner, &vertex) in [triangle.0, triangle.1, triangle.2].iter().enumerate() {
+					faces_at.entry(group[vertex as usize]).or_default().push((face, corner));
+				}
+			}
+
+			for face in range {
+				let normal = face_normal(lod, face);
+				// Read from the original, as the copy may already be rewritten by a material with an overlapping selection.
+				let original = model.lod_levels[lod][face];
+				let triangle = &mut lod_levels[lod][face];
+
+				for (vertex, source) in [&mut triangle.0, &mut triangle.1, &mut triangle.2].iter_mut().zip(&[original.0, original.1, original.2]) {
+					let source = *source as usize;
+
+					let reused = if lod > 0 {
+						first_lod.get(&source).and_then(|candidates| candidates.iter().cloned().max_by(|&a, &b| {
+							let score = |split: usize| splits[split].corners.iter().fold(Vector3::zero(), |sum: Vector3<f32>, &(lod, face, _)| sum + face_normal(lod, face)).dot(normal);
+
+							score(a).partial_cmp(&score(b)).unwrap_or(::std::cmp::Ordering::Equal)
+						}))
+					} else {
+						None
+					};
+
+					let split = match reused {
+						Some(split) => split,
+						None => {
+							let corners = faces_at[&group[source]].iter()
+								.filter(|&&(other, _)| other == face || face_normal(lod, other).dot(normal) >= min_dot)
+								.map(|&(other, corner)| (lod, other, corner))
+								.collect::<Vec<FaceCorner>>();
+
+							let next = splits.len();
+
+							*keys.entry((source, corners.clone())).or_insert_with(|| {
+								splits.push(Split { source: vertex_offset + source, base: first_split, corners });
+
+								if lod == 0 {
+									first_lod.entry(source).or_default().push(next);
+								}
+
+								next
+							})
+						}
+					};
+
+					**vertex = (split - first_split) as u32;
+				}
+			}
+		}
+
+		materials.push((first_split, splits.len() - first_split));
+	}
+
+	for (material, (offset, count)) in model.materials.iter_mut().zip(materials) {
+		material.vertex_offset = offset as u32;
+		material.vertex_count = count as u32;
+	}
+
+	for frame in &mut model.frames {
+		let vertices = splits.iter().map(|split| {
+			let mut normal = Vector3::zero();
+
+			for &(lod, face, corner) in &split.corners {
+				let triangle = lod_levels[lod][face];
+				let position = |index: u32| frame.vertices[splits[split.base + index as usize].source].position;
+
+				let (a, b, c) = match corner {
+					0 => (triangle.0, triangle.1, triangle.2),
+					1 => (triangle.1, triangle.2, triangle.0),
+					_ => (triangle.2, triangle.0, triangle.1)
+				};
+
+				normal += weighted_normal(position(a), position(b), position(c));
+			}
+
+			let source = frame.vertices[split.source];
+
+			v2::Vertex {
+				position: source.position,
+				normal: if normal.magnitude2() > 0.0 {
+					normal.normalize()
+				} else if !source.normal.is_zero() {
+					source.normal
+				} else {
+					Vector3::unit_z()
+				},
+				texture: source.texture
+			}
+		}).collect();
+
+		frame.vertices = vertices;
+	}
+
+	model.lod_levels = lod_levels;
+}
+
+/// The normal of a triangle, weighted by both its area and the angle of the corner at `a`.
+fn weighted_normal(a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Vector3<f32> {
+	let (ab, ac) = (b - a, c - a);
+
+	if ab.magnitude2() == 0.0 || ac.magnitude2() == 0.0 {
+		return Vector3::zero();
+	}
+
+	ab.cross(ac) * ab.angle(ac).0
+}
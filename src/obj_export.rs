@@ -0,0 +1,126 @@
+use bounds::Wireframe;
+use cem::{v2, V2};
+use cgmath::{Point3, Matrix4, Deg, InnerSpace};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::io;
+
+/// Writes a single frame of the first level of detail as an OBJ file. If a material library is given, the file refers
+/// to it and selects the materials named by `material_names`, otherwise the materials are only noted in comments.
+/// With `debug_bounds`, the bounding volume of the frame and the model center follow as separate line objects.
+pub fn convert(cem: &V2, frame_index: usize, mtl: Option<&str>, debug_bounds: bool) -> io::Result<String> {
+	let triangle_data = cem.lod_levels.first().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the model has no levels of detail"))?;
+	let frame = cem.frames.get(frame_index).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Tried to extract frame index {} from a CEM file that only has {} frames", frame_index, cem.frames.len())))?;
+
+	let mut string = String::new();
+
+	if let Some(mtl) = mtl {
+		writeln!(string, "mtllib {}", mtl).unwrap();
+	}
+
+	let transformation = Matrix4::from_angle_x(Deg(-90.0));
+
+	for &v2::Vertex { position, normal, texture } in frame.vertices.iter() {
+
+		let normal = (transformation * normal.normalize().extend(0.0)).truncate();
+		let position = Point3::from_homogeneous(transformation * position.to_homogeneous());
+
+		writeln!(string, "v {} {} {}", position.x, position.y, position.z).unwrap();
+		writeln!(string, "vn {} {} {}", normal.x, normal.y, normal.z).unwrap();
+		writeln!(string, "vt {} {}", texture.x, 1.0 - texture.y).unwrap();
+	}
+
+	let names = material_names(cem);
+
+	for (&v2::Material { ref name, texture, ref triangles, vertex_offset, vertex_count: _vertex_count, ref texture_name }, mtl_name) in cem.materials.iter().zip(names.iter()) {
+		// A material without a selection for the first level of detail has no triangles in it.
+		let triangle_slice = triangles.first().cloned().unwrap_or(v2::TriangleSelection { offset: 0, len: 0 });
+
+		writeln!(string, "# name: {}, texture: {}, texture_name: {}", name, texture, texture_name).unwrap();
+
+		if mtl.is_some() {
+			writeln!(string, "usemtl {}", mtl_name).unwrap();
+		}
+
+		for index in 0..triangle_slice.len {
+			let index = index + triangle_slice.offset;
+			let triangle = triangle_data.get(index as usize).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("material {:?} selects triangle {}, but there are only {}", name, index, triangle_data.len())))?;
+
+			let indices = (
+				vertex_offset + triangle.0 + 1,
+				vertex_offset + triangle.1 + 1,
+				vertex_offset + triangle.2 + 1
+			);
+
+			writeln!(string, "f {}/{}/{} {}/{}/{} {}/{}/{}", indices.0, indices.0, indices.0, indices.1, indices.1, indices.1, indices.2, indices.2, indices.2).unwrap();
+		}
+	}
+
+	if debug_bounds {
+		let first = frame.vertices.len() + 1;
+		let bounds = Wireframe::frame(frame, cem.center);
+
+		write_wireframe(&mut string, &format!("bounds_frame{}", frame_index), &bounds, transformation, first);
+		write_wireframe(&mut string, "center", &Wireframe::center(cem.center, Wireframe::center_size(cem)), transformation, first + bounds.positions.len());
+	}
+
+	Ok(string)
+}
+
+/// Writes a wireframe as an object made of lines, where `first` is the OBJ index of its first position.
+fn write_wireframe(string: &mut String, name: &str, wireframe: &Wireframe, transformation: Matrix4<f32>, first: usize) {
+	writeln!(string, "o {}", name).unwrap();
+
+	for &position in &wireframe.positions {
+		let position = Point3::from_homogeneous(transformation * position.to_homogeneous());
+
+		writeln!(string, "v {} {} {}", position.x, position.y, position.z).unwrap();
+	}
+
+	for line in &wireframe.lines {
+		writeln!(string, "l {} {}", first + line[0] as usize, first + line[1] as usize).unwrap();
+	}
+}
+
+/// Writes a material library with a white diffuse material for every material of the model, using the texture name as
+/// the diffuse map.
+pub fn mtl(cem: &V2) -> String {
+	let mut string = String::new();
+
+	for (material, name) in cem.materials.iter().zip(material_names(cem)) {
+		writeln!(string, "newmtl {}", name).unwrap();
+		writeln!(string, "Kd 1 1 1").unwrap();
+
+		if !material.texture_name.is_empty() {
+			writeln!(string, "map_Kd {}", material.texture_name).unwrap();
+		}
+
+		writeln!(string).unwrap();
+	}
+
+	string
+}
+
+/// Names of the materials as used by `usemtl` and `newmtl`. These can't contain whitespace and have to be unique, so
+/// whitespace is replaced and duplicates get a suffix. Unnamed materials are named after their index.
+fn material_names(cem: &V2) -> Vec<String> {
+	let mut used = HashSet::new();
+
+	cem.materials.iter().enumerate().map(|(index, material)| {
+		let base = if material.name.trim().is_empty() {
+			format!("material{}", index)
+		} else {
+			material.name.trim().replace(char::is_whitespace, "_")
+		};
+
+		let mut name = base.clone();
+		let mut suffix = 2;
+
+		while !used.insert(name.clone()) {
+			name = format!("{}_{}", base, suffix);
+			suffix += 1;
+		}
+
+		name
+	}).collect()
+}
@@ -0,0 +1,249 @@
+use cem::{v2, V2};
+use cem::collider::CenterBuilder;
+use cgmath::{Point2, Point3, Vector3, Matrix4, Deg, InnerSpace, Zero};
+use std::collections::HashMap;
+use std::io;
+use triangulate::triangulate;
+use validate::MAX_VERTICES;
+
+/// A corner of a face, as indices into the position, texture coordinate, and normal arrays.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// The faces of an OBJ file along with the vertex data they reference. Lines and points are only counted.
+struct Obj {
+	positions: Vec<Point3<f32>>,
+	texcoords: Vec<Point2<f32>>,
+	normals: Vec<Vector3<f32>>,
+	faces: Vec<Vec<Corner>>,
+	lines: usize,
+	points: usize
+}
+
+impl Obj {
+	fn parse(source: &str) -> Result<Self, (usize, String)> {
+		let mut obj = Obj {
+			positions: Vec::new(),
+			texcoords: Vec::new(),
+			normals: Vec::new(),
+			faces: Vec::new(),
+			lines: 0,
+			points: 0
+		};
+
+		let mut continued = String::new();
+		let mut first_line = 0;
+
+		for (index, line) in source.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or("");
+
+			// A backslash at the end of a line joins it with the next.
+			if continued.is_empty() {
+				first_line = index + 1;
+			}
+
+			if let Some(line) = line.trim_end().strip_suffix('\\') {
+				continued.push_str(line);
+				continued.push(' ');
+				continue;
+			}
+
+			continued.push_str(line);
+
+			obj.statement(&continued).map_err(|message| (first_line, message))?;
+			continued.clear();
+		}
+
+		Ok(obj)
+	}
+
+	fn statement(&mut self, line: &str) -> Result<(), String> {
+		let mut words = line.split_whitespace();
+
+		let keyword = match words.next() {
+			Some(keyword) => keyword,
+			None => return Ok(())
+		};
+
+		match keyword {
+			"v" => {
+				let [x, y, z] = parse_floats(words, 3)?;
+				self.positions.push(Point3::new(x, y, z));
+			},
+			"vt" => {
+				let [u, v, _] = parse_floats(words, 1)?;
+				self.texcoords.push(Point2::new(u, v));
+			},
+			"vn" => {
+				let [x, y, z] = parse_floats(words, 3)?;
+				self.normals.push(Vector3::new(x, y, z));
+			},
+			"f" => {
+				let face = words.map(|corner| self.corner(corner)).collect::<Result<Vec<Corner>, String>>()?;
+
+				if face.len() < 3 {
+					return Err(format!("face has {} corners, at least 3 are needed", face.len()));
+				}
+
+				self.faces.push(face);
+			},
+			"l" => self.lines += 1,
+			"p" => self.points += 1,
+			// Grouping, materials, and smoothing groups don't affect the geometry.
+			_ => ()
+		}
+
+		Ok(())
+	}
+
+	/// Parses a `v`, `v/vt`, `v//vn`, or `v/vt/vn` reference, resolving negative indices relative to the end of the
+	/// arrays read so far.
+	fn corner(&self, corner: &str) -> Result<Corner, String> {
+		let mut parts = corner.split('/');
+
+		let resolve = |part: Option<&str>, len: usize, what: &str| -> Result<Option<usize>, String> {
+			let part = match part {
+				Some(part) if !part.is_empty() => part,
+				_ => return Ok(None)
+			};
+
+			let index = part.parse::<isize>().map_err(|_| format!("invalid {} index {:?}", what, part))?;
+
+			let resolved = if index < 0 { len as isize + index } else { index - 1 };
+
+			if resolved < 0 || resolved >= len as isize {
+				return Err(format!("{} index {} is out of range, only {} are defined", what, index, len));
+			}
+
+			Ok(Some(resolved as usize))
+		};
+
+		let position = resolve(parts.next(), self.positions.len(), "vertex")?
+			.ok_or_else(|| format!("face corner {:?} is missing a vertex index", corner))?;
+		let texcoord = resolve(parts.next(), self.texcoords.len(), "texture coordinate")?;
+		let normal = resolve(parts.next(), self.normals.len(), "normal")?;
+
+		Ok((position, texcoord, normal))
+	}
+}
+
+/// Parses between `min` and 3 floats, filling in the rest with zeroes. Anything after the third value is ignored, as
+/// some programs append a w component or vertex colors.
+fn parse_floats<'a, I>(words: I, min: usize) -> Result<[f32; 3], String> where I: Iterator<Item=&'a str> {
+	let mut values = [0.0; 3];
+	let mut count = 0;
+
+	for (value, word) in values.iter_mut().zip(words) {
+		*value = word.parse::<f32>().map_err(|_| format!("invalid number {:?}", word))?;
+		count += 1;
+	}
+
+	if count < min {
+		return Err(format!("expected at least {} numbers, got {}", min, count));
+	}
+
+	Ok(values)
+}
+
+/// Converts an OBJ file, triangulating faces with more than three corners. If verbose, the number of triangulated
+/// faces is printed.
+pub fn convert(source: &str, verbose: bool) -> io::Result<V2> {
+	let obj = Obj::parse(source).map_err(
+		|(line, message)| io::Error::new(io::ErrorKind::InvalidData, format!("Error in OBJ file on line {}: {}", line, message))
+	)?;
+
+	if obj.lines > 0 || obj.points > 0 {
+		eprintln!("warning[obj]: skipped {} lines and {} points, only faces are supported", obj.lines, obj.points);
+	}
+
+	let mut triangles = Vec::new();
+	let mut vertices = Vec::new();
+
+	let transformation = Matrix4::from_angle_x(Deg(90.0));
+
+	{
+		let mut vertex_associations = HashMap::new();
+
+		let mut resolve_index = |corner: Corner| {
+			*vertex_associations.entry(corner).or_insert_with(|| {
+				let index = vertices.len();
+
+				let (position, texture, normal) = corner;
+
+				let position = obj.positions[position];
+				let texture = texture.map(|index| obj.texcoords[index]).unwrap_or(Point2::new(0.0, 0.0));
+				// Missing normals are left zeroed, to be recalculated once the whole model is known.
+				let normal = normal.map(|index| (transformation * obj.normals[index].normalize().extend(0.0)).truncate()).unwrap_or(Vector3::zero());
+				let position = Point3::from_homogeneous(transformation * position.to_homogeneous());
+
+				vertices.push(v2::Vertex {
+					position,
+					normal,
+					texture: Point2 { x: texture.x, y: 1.0 - texture.y },
+				});
+
+				index as u32
+			})
+		};
+
+		let mut polygons = 0;
+
+		for face in &obj.faces {
+			if face.len() > 3 {
+				polygons += 1;
+			}
+
+			let positions = face.iter().map(|corner| obj.positions[corner.0]).collect::<Vec<_>>();
+
+			for triangle in triangulate(&positions) {
+				triangles.push((
+					resolve_index(face[triangle[0]]),
+					resolve_index(face[triangle[1]]),
+					resolve_index(face[triangle[2]])
+				));
+			}
+		}
+
+		if verbose && polygons > 0 {
+			eprintln!("triangulated {} faces with more than 3 corners", polygons);
+		}
+	}
+
+	if vertices.len() > MAX_VERTICES {
+		eprintln!("warning[cem]: Vertex count exceeds {}, this will most likely crash the game. You have been warned.", MAX_VERTICES);
+		eprintln!("warning[cem]: {} vertices, {} triangles", vertices.len(), triangles.len());
+	}
+
+	// Create the model
+
+	let mut center_builder = CenterBuilder::begin();
+
+	for vertex in &vertices {
+		center_builder.update(vertex.position);
+	}
+
+	let center = center_builder.build();
+
+	Ok(V2 {
+		center,
+		materials: vec![v2::Material {
+			name: "".to_string(),
+			texture: 0,
+			triangles: vec![
+				v2::TriangleSelection {
+					offset: 0,
+					len: triangles.len() as u32
+				}
+			],
+			vertex_offset: 0,
+			vertex_count: vertices.len() as u32,
+			texture_name: "".to_string()
+		}],
+		lod_levels: vec![
+			triangles
+		],
+		tag_points: vec![],
+		frames: vec![
+			v2::Frame::from_vertices(vertices, vec![], center)
+		]
+	})
+}
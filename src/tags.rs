@@ -0,0 +1,168 @@
+use cem::V2;
+use cgmath::Point3;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// The frames that an edit of a tag point applies to, written as `all` or a frame index.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FrameSelection {
+	All,
+	Frame(usize)
+}
+
+impl FromStr for FrameSelection {
+	type Err = String;
+
+	fn from_str(frames: &str) -> Result<Self, Self::Err> {
+		if frames.eq_ignore_ascii_case("all") {
+			return Ok(FrameSelection::All);
+		}
+
+		frames.parse::<usize>().map(FrameSelection::Frame).map_err(|_| format!("expected all or a frame index, got {:?}", frames))
+	}
+}
+
+/// A light emitted from a tag point. The game reads lights from tag points named `light_R_G_B_I_J_K`, where R, G, and
+/// B are the color from 0 to 255, and I, J, and K are three more numbers whose meaning is not known.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Light {
+	pub color: [u32; 3],
+	pub unknown: [u32; 3]
+}
+
+impl Light {
+	/// The color with each channel scaled from 0 to 1.
+	pub fn rgb(&self) -> [f32; 3] {
+		[self.color[0] as f32 / 255.0, self.color[1] as f32 / 255.0, self.color[2] as f32 / 255.0]
+	}
+}
+
+impl fmt::Display for Light {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let ([r, g, b], [i, j, k]) = (self.color, self.unknown);
+
+		write!(f, "light_{}_{}_{}_{}_{}_{}", r, g, b, i, j, k)
+	}
+}
+
+impl FromStr for Light {
+	type Err = &'static str;
+
+	fn from_str(definition: &str) -> Result<Self, Self::Err> {
+		let mut split = definition.split('_');
+
+		if split.next() != Some("light") {
+			return Err("Invalid light definition");
+		}
+
+		let mut numbers = [0; 6];
+
+		for number in &mut numbers {
+			*number = split.next().ok_or("Invalid light definition")?.parse::<u32>().map_err(|_| "failed to parse number")?;
+		}
+
+		Ok(Light {
+			color: [numbers[0], numbers[1], numbers[2]],
+			unknown: [numbers[3], numbers[4], numbers[5]]
+		})
+	}
+}
+
+/// Parses three numbers from 0 to 255 written as `r,g,b`, or a color in hexadecimal written as `#rrggbb`.
+pub fn parse_color(color: &str) -> Result<[u32; 3], String> {
+	let invalid = || format!("expected a color such as 255,200,64 or #ffc840, got {:?}", color);
+
+	if let Some(hex) = color.strip_prefix('#') {
+		if hex.len() != 6 || !hex.is_ascii() {
+			return Err(invalid());
+		}
+
+		let channel = |index: usize| u32::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| invalid());
+
+		return Ok([channel(0)?, channel(1)?, channel(2)?]);
+	}
+
+	let channels = color.split(',').map(|channel| channel.trim().parse::<u8>()).collect::<Result<Vec<u8>, _>>().map_err(|_| invalid())?;
+
+	match channels[..] {
+		[r, g, b] => Ok([r as u32, g as u32, b as u32]),
+		_ => Err(invalid())
+	}
+}
+
+/// Adds a tag point to the model at the position in the selected frames. Every frame needs a position for it, so with a
+/// single frame selected, the other frames place it at the origin until it is moved there with `move_to`.
+pub fn add(model: &mut V2, name: &str, position: Point3<f32>, frames: FrameSelection) -> io::Result<()> {
+	if model.tag_points.iter().any(|existing| existing == name) {
+		return Err(invalid(format!("there already is a tag point named {:?}", name)));
+	}
+
+	check_frame(model, frames)?;
+
+	model.tag_points.push(name.to_owned());
+
+	for (frame_index, frame) in model.frames.iter_mut().enumerate() {
+		let selected = frames == FrameSelection::All || frames == FrameSelection::Frame(frame_index);
+
+		frame.tag_points.push(if selected { position } else { Point3::new(0.0, 0.0, 0.0) });
+	}
+
+	Ok(())
+}
+
+/// Renames a tag point, keeping its positions.
+pub fn rename(model: &mut V2, name: &str, new_name: &str) -> io::Result<()> {
+	let index = find(model, name)?;
+
+	if name != new_name && model.tag_points.iter().any(|existing| existing == new_name) {
+		return Err(invalid(format!("there already is a tag point named {:?}", new_name)));
+	}
+
+	model.tag_points[index] = new_name.to_owned();
+
+	Ok(())
+}
+
+/// Removes a tag point from the model and every frame.
+pub fn remove(model: &mut V2, name: &str) -> io::Result<()> {
+	let index = find(model, name)?;
+
+	model.tag_points.remove(index);
+
+	for frame in &mut model.frames {
+		frame.tag_points.remove(index);
+	}
+
+	Ok(())
+}
+
+/// Moves a tag point to a position in the selected frames.
+pub fn move_to(model: &mut V2, name: &str, position: Point3<f32>, frames: FrameSelection) -> io::Result<()> {
+	let index = find(model, name)?;
+
+	check_frame(model, frames)?;
+
+	for (frame_index, frame) in model.frames.iter_mut().enumerate() {
+		if frames == FrameSelection::All || frames == FrameSelection::Frame(frame_index) {
+			frame.tag_points[index] = position;
+		}
+	}
+
+	Ok(())
+}
+
+fn find(model: &V2, name: &str) -> io::Result<usize> {
+	model.tag_points.iter().position(|existing| existing == name).ok_or_else(|| invalid(format!("there is no tag point named {:?}", name)))
+}
+
+fn check_frame(model: &V2, frames: FrameSelection) -> io::Result<()> {
+	match frames {
+		FrameSelection::Frame(index) if index >= model.frames.len() => Err(invalid(format!("the model has only {} frames, there is no frame {}", model.frames.len(), index))),
+		_ => Ok(())
+	}
+}
+
+fn invalid<M>(message: M) -> io::Error where M: Into<String> {
+	io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
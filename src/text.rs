@@ -0,0 +1,220 @@
+use cem::{v2, V2, Scene};
+use cem::collider::{Aabb, Collider};
+use cgmath::{Point2, Point3, Vector3, Matrix4, SquareMatrix};
+use ron::{self, extensions::Extensions};
+use serde_json;
+use std::io::{self, Read, Write};
+
+/// A complete CEM file in a form suited for text formats like JSON and RON. Every field of the binary format is kept,
+/// so reading a CEM file, writing it as text, and reading that text back produces an identical CEM file.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Document {
+	pub version: String,
+	pub scene: SceneText
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SceneText {
+	pub name: String,
+	pub center: [f32; 3],
+	pub materials: Vec<MaterialText>,
+	/// Triangles of each level of detail, as vertex indices relative to the material that selects them.
+	pub lod_levels: Vec<Vec<[u32; 3]>>,
+	pub tag_points: Vec<String>,
+	pub frames: Vec<FrameText>,
+	#[serde(default)]
+	pub children: Vec<SceneText>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialText {
+	pub name: String,
+	pub texture: u32,
+	pub texture_name: String,
+	pub vertex_offset: u32,
+	pub vertex_count: u32,
+	/// Triangles of each level of detail used by the material, as (offset, len) pairs.
+	pub triangles: Vec<[u32; 2]>
+}
+
+/// A frame of the model. The transform defaults to the identity, and the bounds are recalculated from the vertices
+/// if they are left out, so that hand edited frames don't need to keep them up to date.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FrameText {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub transform: Option<[[f32; 4]; 4]>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub bounds: Option<BoundsText>,
+	/// Positions of the tag points, in the order of their names.
+	pub tag_points: Vec<[f32; 3]>,
+	pub vertices: Vec<VertexText>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BoundsText {
+	pub lower: [f32; 3],
+	pub upper: [f32; 3],
+	pub radius: f32
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct VertexText {
+	pub position: [f32; 3],
+	pub normal: [f32; 3],
+	pub texture: [f32; 2]
+}
+
+impl Document {
+	pub fn new(scene: &Scene<V2>) -> Self {
+		Document {
+			version: "2.0".to_owned(),
+			scene: SceneText::new(scene)
+		}
+	}
+
+	pub fn into_scene(self) -> io::Result<Scene<V2>> {
+		if self.version != "2.0" {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Cannot read CEM version {} text files yet, only 2.0 is supported", self.version)));
+		}
+
+		Ok(self.scene.into_scene())
+	}
+}
+
+impl SceneText {
+	fn new(scene: &Scene<V2>) -> Self {
+		let model = &scene.model;
+
+		SceneText {
+			name: scene.name.clone(),
+			center: model.center.into(),
+			materials: model.materials.iter().map(|material| MaterialText {
+				name: material.name.clone(),
+				texture: material.texture,
+				texture_name: material.texture_name.clone(),
+				vertex_offset: material.vertex_offset,
+				vertex_count: material.vertex_count,
+				triangles: material.triangles.iter().map(|selection| [selection.offset, selection.len]).collect()
+			}).collect(),
+			lod_levels: model.lod_levels.iter()
+				.map(|triangles| triangles.iter().map(|triangle| [triangle.0, triangle.1, triangle.2]).collect())
+				.collect(),
+			tag_points: model.tag_points.clone(),
+			frames: model.frames.iter().map(|frame| FrameText {
+				transform: Some(frame.transform.into()),
+				bounds: Some(BoundsText {
+					lower: frame.collider.aabb.lower.into(),
+					upper: frame.collider.aabb.upper.into(),
+					radius: frame.collider.radius
+				}),
+				tag_points: frame.tag_points.iter().map(|&position| position.into()).collect(),
+				vertices: frame.vertices.iter().map(|vertex| VertexText {
+					position: vertex.position.into(),
+					normal: vertex.normal.into(),
+					texture: vertex.texture.into()
+				}).collect()
+			}).collect(),
+			children: scene.children.iter().map(SceneText::new).collect()
+		}
+	}
+
+	fn into_scene(self) -> Scene<V2> {
+		let center = Point3::from(self.center);
+
+		let model = V2 {
+			center,
+			lod_levels: self.lod_levels.into_iter()
+				.map(|triangles| triangles.into_iter().map(|triangle| (triangle[0], triangle[1], triangle[2])).collect())
+				.collect(),
+			materials: self.materials.into_iter().map(|material| v2::Material {
+				name: material.name,
+				texture: material.texture,
+				triangles: material.triangles.iter().map(|selection| v2::TriangleSelection { offset: selection[0], len: selection[1] }).collect(),
+				vertex_offset: material.vertex_offset,
+				vertex_count: material.vertex_count,
+				texture_name: material.texture_name
+			}).collect(),
+			tag_points: self.tag_points,
+			frames: self.frames.into_iter().map(|frame| {
+				let vertices = frame.vertices.iter().map(|vertex| v2::Vertex {
+					position: Point3::from(vertex.position),
+					normal: Vector3::from(vertex.normal),
+					texture: Point2::from(vertex.texture)
+				}).collect();
+
+				let tag_points = frame.tag_points.iter().map(|&position| Point3::from(position)).collect();
+
+				let mut result = v2::Frame::from_vertices(vertices, tag_points, center);
+
+				result.transform = frame.transform.map(Matrix4::from).unwrap_or_else(Matrix4::identity);
+
+				if let Some(bounds) = frame.bounds {
+					result.collider = Collider {
+						aabb: Aabb { lower: Point3::from(bounds.lower), upper: Point3::from(bounds.upper) },
+						radius: bounds.radius
+					};
+				}
+
+				result
+			}).collect()
+		};
+
+		Scene {
+			name: self.name,
+			model,
+			children: self.children.into_iter().map(SceneText::into_scene).collect()
+		}
+	}
+
+	/// Whether every number can be represented in JSON, which has no infinities or NaN.
+	fn is_finite(&self) -> bool {
+		let finite = |values: &[f32]| values.iter().all(|value| value.is_finite());
+
+		finite(&self.center) && self.frames.iter().all(|frame| {
+			frame.transform.is_none_or(|transform| transform.iter().all(|column| finite(column))) &&
+				frame.bounds.as_ref().is_none_or(|bounds| finite(&bounds.lower) && finite(&bounds.upper) && bounds.radius.is_finite()) &&
+				frame.tag_points.iter().all(|position| finite(position)) &&
+				frame.vertices.iter().all(|vertex| finite(&vertex.position) && finite(&vertex.normal) && finite(&vertex.texture))
+		}) && self.children.iter().all(SceneText::is_finite)
+	}
+}
+
+pub fn write_json<W>(scene: &Scene<V2>, mut w: W) -> io::Result<()> where W: Write {
+	let document = Document::new(scene);
+
+	if !document.scene.is_finite() {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "the model contains infinite or NaN values, which cannot be stored in JSON, use RON instead"));
+	}
+
+	serde_json::to_writer_pretty(&mut w, &document)?;
+	writeln!(w)
+}
+
+pub fn read_json<R>(r: R) -> io::Result<Scene<V2>> where R: Read {
+	let document: Document = serde_json::from_reader(r)?;
+
+	document.into_scene()
+}
+
+pub fn write_ron<W>(scene: &Scene<V2>, mut w: W) -> io::Result<()> where W: Write {
+	// Leaves out the Some(...) around optional fields, which makes hand editing easier.
+	let config = ron::ser::PrettyConfig::new().extensions(Extensions::IMPLICIT_SOME);
+	let text = ron::ser::to_string_pretty(&Document::new(scene), config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+	writeln!(w, "{}", text)
+}
+
+pub fn read_ron<R>(mut r: R) -> io::Result<Scene<V2>> where R: Read {
+	let mut buffer = String::new();
+	r.read_to_string(&mut buffer)?;
+
+	let document: Document = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME).from_str(&buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+	document.into_scene()
+}
@@ -0,0 +1,162 @@
+use cem::{V2, Scene};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A directory to look for the textures of exported models in, such as the data folder of the game. The directory is
+/// searched once when it is opened. Clones share the textures placed so far, so that the models of a batch that are
+/// written to the same directory, even in parallel, don't overwrite each other's textures.
+#[derive(Debug, Clone)]
+pub struct TextureDir {
+	pub path: PathBuf,
+	/// Link to the textures instead of copying them.
+	pub symlink: bool,
+	index: Arc<HashMap<String, PathBuf>>,
+	/// The texture placed at each destination, by lowercase path.
+	placed: Arc<Mutex<HashMap<PathBuf, PathBuf>>>
+}
+
+impl TextureDir {
+	pub fn open(path: PathBuf, symlink: bool) -> io::Result<Self> {
+		let index = index(&path).map_err(|e| io::Error::new(e.kind(), format!("failed to search the texture directory {} ({})", path.display(), e)))?;
+
+		Ok(TextureDir { path, symlink, index: Arc::new(index), placed: Arc::new(Mutex::new(HashMap::new())) })
+	}
+}
+
+/// Finds the texture of every material of the scene in the texture directory, copies or links it into the output
+/// directory, and changes the texture name to the file next to the output. Textures are looked up by their path
+/// relative to the texture directory, then by file name anywhere inside it, ignoring ASCII case either way as game
+/// data folders mix upper and lower case. Missing textures are reported, and keep their original names.
+pub fn package(scene: &mut Scene<V2>, texture_dir: &TextureDir, output_dir: &Path) -> io::Result<()> {
+	package_scene(scene, texture_dir, output_dir, &mut HashMap::new())
+}
+
+/// Every file in the directory and its subdirectories, by lowercase path relative to the directory.
+fn index(root: &Path) -> io::Result<HashMap<String, PathBuf>> {
+	let mut files = HashMap::new();
+	let mut pending = vec![root.to_owned()];
+
+	while let Some(dir) = pending.pop() {
+		for entry in fs::read_dir(&dir)? {
+			let path = entry?.path();
+
+			if path.is_dir() {
+				pending.push(path);
+			} else {
+				let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/").to_ascii_lowercase();
+
+				files.insert(relative, path);
+			}
+		}
+	}
+
+	Ok(files)
+}
+
+fn find<'i>(index: &'i HashMap<String, PathBuf>, texture_name: &str) -> Option<&'i PathBuf> {
+	let relative = texture_name.replace('\\', "/").trim_start_matches('/').to_ascii_lowercase();
+	let file_name = relative.rsplit('/').next().unwrap_or(&relative).to_owned();
+
+	index.get(&relative).or_else(|| {
+		// Sorting keeps the choice stable when more than one directory has a file of the same name.
+		let mut matches = index.iter().filter(|&(path, _)| path.rsplit('/').next() == Some(&file_name as &str)).collect::<Vec<_>>();
+		matches.sort();
+
+		matches.first().map(|&(_, path)| path)
+	})
+}
+
+fn package_scene(
+	scene: &mut Scene<V2>,
+	texture_dir: &TextureDir,
+	output_dir: &Path,
+	packaged: &mut HashMap<String, Option<String>>
+) -> io::Result<()> {
+	for material in &mut scene.model.materials {
+		if material.texture_name.is_empty() {
+			continue;
+		}
+
+		if !packaged.contains_key(&material.texture_name) {
+			let result = match find(&texture_dir.index, &material.texture_name) {
+				Some(source) => Some(place(source, texture_dir, output_dir)?),
+				None => {
+					eprintln!("warning[texture]: could not find texture {:?} of material {:?} in {}", material.texture_name, material.name, texture_dir.path.display());
+					None
+				}
+			};
+
+			packaged.insert(material.texture_name.clone(), result);
+		}
+
+		if let Some(ref name) = packaged[&material.texture_name] {
+			material.texture_name = name.clone();
+		}
+	}
+
+	for child in &mut scene.children {
+		package_scene(child, texture_dir, output_dir, packaged)?;
+	}
+
+	Ok(())
+}
+
+/// Copies or links a texture into the output directory, returning its new file name. Different textures with the
+/// same file name are given a numbered suffix.
+fn place(source: &Path, texture_dir: &TextureDir, output_dir: &Path) -> io::Result<String> {
+	// Held while copying, so that another model never refers to a texture that is still being written.
+	let mut placed = texture_dir.placed.lock().unwrap_or_else(PoisonError::into_inner);
+
+	let stem = source.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+	let extension = source.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+
+	let mut name = format!("{}{}", stem, extension);
+	let mut suffix = 2;
+
+	while let Some(existing) = placed.get(&output_dir.join(name.to_ascii_lowercase())) {
+		if existing == source {
+			return Ok(name);
+		}
+
+		name = format!("{}_{}{}", stem, suffix, extension);
+		suffix += 1;
+	}
+
+	let destination = output_dir.join(&name);
+
+	// Exporting into the texture directory leaves the textures where they are.
+	if !(destination.exists() && destination.canonicalize()? == source.canonicalize()?) {
+		let result = if texture_dir.symlink { link(source, &destination) } else { fs::copy(source, &destination).map(|_| ()) };
+
+		result.map_err(|e| io::Error::new(e.kind(), format!("failed to place texture {} at {} ({})", source.display(), destination.display(), e)))?;
+	}
+
+	placed.insert(output_dir.join(name.to_ascii_lowercase()), source.to_owned());
+
+	Ok(name)
+}
+
+#[cfg(unix)]
+fn link(source: &Path, destination: &Path) -> io::Result<()> {
+	let source = source.canonicalize()?;
+
+	if fs::read_link(destination).ok().as_ref() == Some(&source) {
+		return Ok(());
+	}
+
+	if fs::symlink_metadata(destination).is_ok() {
+		fs::remove_file(destination)?;
+	}
+
+	::std::os::unix::fs::symlink(source, destination)
+}
+
+#[cfg(not(unix))]
+fn link(source: &Path, destination: &Path) -> io::Result<()> {
+	eprintln!("warning[texture]: symbolic links are not supported on this platform, copying {} instead", source.display());
+
+	fs::copy(source, destination).map(|_| ())
+}
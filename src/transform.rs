@@ -0,0 +1,146 @@
+use cem::{V2, Scene};
+use cgmath::{Point3, Vector3, Matrix, Matrix3, Matrix4, Deg, SquareMatrix, InnerSpace};
+use std::str::FromStr;
+
+/// A coordinate axis of the model.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Axis {
+	X,
+	Y,
+	Z
+}
+
+impl Axis {
+	pub const NAMES: &'static [&'static str] = &["x", "y", "z"];
+
+	fn unit(self) -> Vector3<f32> {
+		match self {
+			Axis::X => Vector3::unit_x(),
+			Axis::Y => Vector3::unit_y(),
+			Axis::Z => Vector3::unit_z()
+		}
+	}
+}
+
+impl FromStr for Axis {
+	type Err = String;
+
+	fn from_str(axis: &str) -> Result<Self, Self::Err> {
+		match &axis.to_ascii_lowercase() as &str {
+			"x" => Ok(Axis::X),
+			"y" => Ok(Axis::Y),
+			"z" => Ok(Axis::Z),
+			_ => Err(format!("expected x, y, or z, got {:?}", axis))
+		}
+	}
+}
+
+/// A rotation around one of the axes, written as `axis:degrees`, such as `z:90`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rotation {
+	pub axis: Axis,
+	pub angle: Deg<f32>
+}
+
+impl FromStr for Rotation {
+	type Err = String;
+
+	fn from_str(rotation: &str) -> Result<Self, Self::Err> {
+		let mut parts = rotation.splitn(2, ':');
+
+		let axis = parts.next().unwrap_or("").parse::<Axis>()?;
+		let angle = parts.next().and_then(|angle| angle.parse::<f32>().ok()).filter(|angle| angle.is_finite())
+			.ok_or_else(|| format!("expected a rotation such as z:90, got {:?}", rotation))?;
+
+		Ok(Rotation { axis, angle: Deg(angle) })
+	}
+}
+
+/// Parses a vector written as `x,y,z`.
+pub fn parse_vector(vector: &str) -> Result<Vector3<f32>, String> {
+	let values = vector.split(',').map(|value| value.trim().parse::<f32>()).collect::<Result<Vec<f32>, _>>();
+
+	match values {
+		Ok(ref values) if values.len() == 3 && values.iter().all(|value| value.is_finite()) => Ok(Vector3::new(values[0], values[1], values[2])),
+		_ => Err(format!("expected three numbers such as 1,0,-2.5, got {:?}", vector))
+	}
+}
+
+/// Parses a scale, either a single factor for every axis or a factor per axis written as `x,y,z`. Zero factors are
+/// rejected, as they would flatten the model and leave the normals undefined.
+pub fn parse_scale(text: &str) -> Result<Vector3<f32>, String> {
+	let scale = match text.parse::<f32>() {
+		Ok(factor) => Vector3::new(factor, factor, factor),
+		Err(_) => parse_vector(text)?
+	};
+
+	if scale.x.is_normal() && scale.y.is_normal() && scale.z.is_normal() {
+		Ok(scale)
+	} else {
+		Err(format!("scale factors must be finite and not zero, got {:?}", text))
+	}
+}
+
+/// Combines the operations into a single matrix. They are applied in a fixed order no matter how they were given:
+/// first the scale, then the mirrors, then the rotations in order, and finally the translation.
+pub fn matrix(scale: Vector3<f32>, mirror: &[Axis], rotate: &[Rotation], translate: Vector3<f32>) -> Matrix4<f32> {
+	let mut matrix = Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+
+	for &axis in mirror {
+		let flip = Vector3::new(1.0, 1.0, 1.0) - axis.unit() * 2.0;
+
+		matrix = Matrix4::from_nonuniform_scale(flip.x, flip.y, flip.z) * matrix;
+	}
+
+	for rotation in rotate {
+		matrix = Matrix4::from_axis_angle(rotation.axis.unit(), rotation.angle) * matrix;
+	}
+
+	Matrix4::from_translation(translate) * matrix
+}
+
+/// Transforms every vertex and tag point in every frame of the scene and its children. Normals are transformed with
+/// the inverse transpose so that they stay perpendicular to the surface, and the winding of the triangles is flipped
+/// if the matrix mirrors the model, so that they keep facing outwards. The transform of each frame becomes
+/// `matrix * transform * inverse(matrix)`, so that child models placed by it stay attached to the transformed parent.
+/// The center and bounds are left as they were, see `bounds::recompute`.
+pub fn apply(scene: &mut Scene<V2>, matrix: Matrix4<f32>) {
+	let linear = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+	let normal_matrix = linear.invert().map(|inverse| inverse.transpose()).unwrap_or(linear);
+	let point = |point: Point3<f32>| Point3::from_homogeneous(matrix * point.to_homogeneous());
+	let inverse = matrix.invert();
+
+	let model = &mut scene.model;
+
+	for frame in &mut model.frames {
+		for vertex in &mut frame.vertices {
+			vertex.position = point(vertex.position);
+
+			let normal = normal_matrix * vertex.normal;
+
+			if normal.magnitude2() > 0.0 {
+				vertex.normal = normal.normalize();
+			}
+		}
+
+		for tag_point in &mut frame.tag_points {
+			*tag_point = point(*tag_point);
+		}
+
+		if let Some(inverse) = inverse {
+			frame.transform = matrix * frame.transform * inverse;
+		}
+	}
+
+	if linear.determinant() < 0.0 {
+		for triangles in &mut model.lod_levels {
+			for triangle in triangles {
+				*triangle = (triangle.0, triangle.2, triangle.1);
+			}
+		}
+	}
+
+	for child in &mut scene.children {
+		apply(child, matrix);
+	}
+}
@@ -0,0 +1,96 @@
+use cgmath::{Point2, Point3, Vector3, InnerSpace};
+
+/// Splits a polygon into triangles by ear clipping, returning indices into `polygon`. The winding of the polygon is
+/// preserved, and concave polygons are handled as long as they don't intersect themselves. Polygons without a usable
+/// plane (such as ones where every corner is on a line) fall back to a fan around the first corner.
+pub fn triangulate(polygon: &[Point3<f32>]) -> Vec<[usize; 3]> {
+	if polygon.len() < 3 {
+		return Vec::new();
+	}
+
+	if polygon.len() == 3 {
+		return vec![[0, 1, 2]];
+	}
+
+	let normal = newell_normal(polygon);
+
+	if !normal.magnitude2().is_normal() {
+		return fan(polygon.len());
+	}
+
+	// Project the polygon onto the axis plane that it is most parallel to, keeping a counter-clockwise winding.
+	let (axis, sign) = {
+		let abs = Vector3::new(normal.x.abs(), normal.y.abs(), normal.z.abs());
+
+		if abs.x >= abs.y && abs.x >= abs.z {
+			(0, normal.x.signum())
+		} else if abs.y >= abs.z {
+			(1, normal.y.signum())
+		} else {
+			(2, normal.z.signum())
+		}
+	};
+
+	let projected = polygon.iter().map(|point| match axis {
+		0 => Point2::new(point.y, point.z * sign),
+		1 => Point2::new(point.z, point.x * sign),
+		_ => Point2::new(point.x, point.y * sign)
+	}).collect::<Vec<_>>();
+
+	let mut remaining = (0..polygon.len()).collect::<Vec<usize>>();
+	let mut triangles = Vec::with_capacity(polygon.len() - 2);
+
+	while remaining.len() > 3 {
+		let len = remaining.len();
+
+		let ear = (0..len).find(|&i| {
+			let (a, b, c) = (remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]);
+
+			if cross(projected[a], projected[b], projected[c]) <= 0.0 {
+				return false;
+			}
+
+			!remaining.iter()
+				.filter(|&&other| other != a && other != b && other != c)
+				.any(|&other| inside(projected[other], projected[a], projected[b], projected[c]))
+		});
+
+		// A self intersecting or degenerate polygon may not have any ears left, so just clip the first corner.
+		let i = ear.unwrap_or(0);
+
+		triangles.push([remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]]);
+		remaining.remove(i);
+	}
+
+	triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+	triangles
+}
+
+fn fan(len: usize) -> Vec<[usize; 3]> {
+	(1..len - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+fn newell_normal(polygon: &[Point3<f32>]) -> Vector3<f32> {
+	let mut normal = Vector3::new(0.0, 0.0, 0.0);
+
+	for (index, current) in polygon.iter().enumerate() {
+		let next = polygon[(index + 1) % polygon.len()];
+
+		normal.x += (current.y - next.y) * (current.z + next.z);
+		normal.y += (current.z - next.z) * (current.x + next.x);
+		normal.z += (current.x - next.x) * (current.y + next.y);
+	}
+
+	normal
+}
+
+/// Twice the signed area of the triangle, positive for counter-clockwise triangles.
+fn cross(a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> f32 {
+	(b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// Whether `point` is inside or on the edge of the counter-clockwise triangle `abc`.
+fn inside(point: Point2<f32>, a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> bool {
+	cross(a, b, point) >= 0.0 && cross(b, c, point) >= 0.0 && cross(c, a, point) >= 0.0
+}
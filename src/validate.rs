@@ -0,0 +1,205 @@
+use cem::{V2, Scene};
+use std::fmt;
+use std::io;
+
+/// It appears that there is some limit on the vertex count. This needs to be investigated further, but it appears that
+/// adding more than 2442 instantly crashes the game on model load.
+pub const MAX_VERTICES: usize = 2442;
+
+/// How far the length of a normal may be from 1 before it is reported.
+const NORMAL_TOLERANCE: f32 = 1.0e-3;
+
+/// How bad a problem is. Errors are broken references or inconsistent layouts that the converters can't handle, while
+/// warnings only risk unexpected results in the game.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+	Error,
+	Warning
+}
+
+/// A problem found in a CEM file, along with where it was found.
+#[derive(Debug)]
+pub struct Problem {
+	pub severity: Severity,
+	pub location: String,
+	pub message: String
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let severity = match self.severity {
+			Severity::Error => "error",
+			Severity::Warning => "warning"
+		};
+
+		write!(f, "{}: {}: {}", severity, self.location, self.message)
+	}
+}
+
+/// Checks the scene and all of its children for broken references, inconsistent frames, invalid vertex data, and
+/// known engine limits. An empty result means that no problems were found.
+pub fn validate(scene: &Scene<V2>) -> Vec<Problem> {
+	let mut problems = Vec::new();
+
+	validate_scene(scene, &format!("scene {:?}", scene.name), &mut problems);
+
+	problems
+}
+
+/// Fails with the first error found in the scene. Models have to pass this before they are processed any further, as
+/// the converters index vertices and triangles through the counts and offsets stored in the file.
+pub fn check(scene: &Scene<V2>) -> io::Result<()> {
+	match validate(scene).into_iter().find(|problem| problem.severity == Severity::Error) {
+		Some(problem) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", problem.location, problem.message))),
+		None => Ok(())
+	}
+}
+
+fn validate_scene(scene: &Scene<V2>, location: &str, problems: &mut Vec<Problem>) {
+	let model = &scene.model;
+
+	let mut report = |severity: Severity, location: String, message: String| problems.push(Problem { severity, location, message });
+
+	check_name(&scene.name, || location.to_owned(), &mut report);
+
+	if model.materials.is_empty() {
+		report(Severity::Error, location.to_owned(), "model has no materials".to_owned());
+	}
+
+	if model.lod_levels.is_empty() {
+		report(Severity::Error, location.to_owned(), "model has no levels of detail".to_owned());
+	}
+
+	if model.frames.is_empty() {
+		report(Severity::Error, location.to_owned(), "model has no frames".to_owned());
+	}
+
+	if !(model.center.x.is_finite() && model.center.y.is_finite() && model.center.z.is_finite()) {
+		report(Severity::Warning, location.to_owned(), format!("center {:?} is not finite", model.center));
+	}
+
+	let vertex_count = model.frames.first().map(|frame| frame.vertices.len()).unwrap_or(0);
+
+	if vertex_count > MAX_VERTICES {
+		report(Severity::Warning, location.to_owned(), format!("{} vertices exceed the limit of {}, this will most likely crash the game", vertex_count, MAX_VERTICES));
+	}
+
+	for (index, name) in model.tag_points.iter().enumerate() {
+		check_name(name, || format!("{}, tag point {}", location, index), &mut report);
+	}
+
+	// Materials
+
+	for (index, material) in model.materials.iter().enumerate() {
+		let location = format!("{}, material {} ({:?})", location, index, material.name);
+
+		check_name(&material.name, || location.clone(), &mut report);
+		check_name(&material.texture_name, || location.clone(), &mut report);
+
+		let vertex_end = material.vertex_offset as usize + material.vertex_count as usize;
+
+		if vertex_end > vertex_count {
+			report(Severity::Error, location.clone(), format!("vertex range {}..{} exceeds the {} vertices of the model", material.vertex_offset, vertex_end, vertex_count));
+		}
+
+		if material.triangles.len() != model.lod_levels.len() {
+			report(Severity::Error, location.clone(), format!("has triangle selections for {} levels of detail, but the model has {}", material.triangles.len(), model.lod_levels.len()));
+		}
+
+		for (lod, (selection, triangles)) in material.triangles.iter().zip(model.lod_levels.iter()).enumerate() {
+			let start = selection.offset as usize;
+			let end = start + selection.len as usize;
+
+			if end > triangles.len() {
+				report(Severity::Error, format!("{}, lod {}", location, lod), format!("triangle selection {}..{} exceeds the {} triangles of the level", start, end, triangles.len()));
+				continue;
+			}
+
+			for (triangle_index, triangle) in triangles[start..end].iter().enumerate() {
+				for &vertex in &[triangle.0, triangle.1, triangle.2] {
+					if vertex >= material.vertex_count {
+						report(
+							Severity::Error,
+							format!("{}, lod {}, triangle {}", location, lod, start + triangle_index),
+							format!("vertex index {} is outside of the {} vertices of the material", vertex, material.vertex_count)
+						);
+					}
+				}
+			}
+		}
+	}
+
+	// Triangle selections of different materials must not overlap within a level of detail.
+
+	for lod in 0..model.lod_levels.len() {
+		let mut selections = model.materials.iter().enumerate()
+			.filter_map(|(index, material)| material.triangles.get(lod).map(|selection| (selection.offset as usize, selection.offset as usize + selection.len as usize, index)))
+			.filter(|&(start, end, _)| start < end)
+			.collect::<Vec<_>>();
+
+		selections.sort();
+
+		for pair in selections.windows(2) {
+			let ((_, previous_end, previous), (start, _, current)) = (pair[0], pair[1]);
+
+			if start < previous_end {
+				report(Severity::Warning, format!("{}, lod {}", location, lod), format!("triangle selections of materials {} and {} overlap", previous, current));
+			}
+		}
+	}
+
+	// Frames
+
+	for (index, frame) in model.frames.iter().enumerate() {
+		let location = format!("{}, frame {}", location, index);
+
+		if frame.vertices.len() != vertex_count {
+			report(Severity::Error, location.clone(), format!("has {} vertices, but the first frame has {}", frame.vertices.len(), vertex_count));
+		}
+
+		if frame.tag_points.len() != model.tag_points.len() {
+			report(Severity::Error, location.clone(), format!("has {} tag points, but the model has {}", frame.tag_points.len(), model.tag_points.len()));
+		}
+
+		for (vertex_index, vertex) in frame.vertices.iter().enumerate() {
+			let position = vertex.position;
+			let normal = vertex.normal;
+			let texture = vertex.texture;
+
+			if !(position.x.is_finite() && position.y.is_finite() && position.z.is_finite()) {
+				report(Severity::Warning, format!("{}, vertex {}", location, vertex_index), format!("position {:?} is not finite", position));
+			}
+
+			let length = (normal.x * normal.x + normal.y * normal.y + normal.z * normal.z).sqrt();
+
+			if !length.is_finite() || (length - 1.0).abs() > NORMAL_TOLERANCE {
+				report(Severity::Warning, format!("{}, vertex {}", location, vertex_index), format!("normal {:?} has length {} instead of 1", normal, length));
+			}
+
+			if !(texture.x.is_finite() && texture.y.is_finite()) {
+				report(Severity::Warning, format!("{}, vertex {}", location, vertex_index), format!("texture coordinate {:?} is not finite", texture));
+			}
+		}
+
+		for (tag_index, tag_point) in frame.tag_points.iter().enumerate() {
+			if !(tag_point.x.is_finite() && tag_point.y.is_finite() && tag_point.z.is_finite()) {
+				report(Severity::Warning, format!("{}, tag point {}", location, tag_index), format!("position {:?} is not finite", tag_point));
+			}
+		}
+	}
+
+	for (index, child) in scene.children.iter().enumerate() {
+		validate_scene(child, &format!("{} > child {} ({:?})", location, index, child.name), problems);
+	}
+}
+
+/// Strings are stored as NUL terminated ISO-8859-1, so anything else is silently mangled on write.
+fn check_name<L, R>(name: &str, location: L, report: &mut R) where L: Fn() -> String, R: FnMut(Severity, String, String) {
+	if name.contains('\0') {
+		report(Severity::Warning, location(), format!("name {:?} contains a NUL character and will be truncated", name));
+	}
+
+	if name.chars().any(|c| c > '\u{FF}') {
+		report(Severity::Warning, location(), format!("name {:?} contains characters outside of ISO-8859-1, which will be replaced", name));
+	}
+}
@@ -0,0 +1,150 @@
+use cem::{v2, V2, Scene};
+use std::collections::{HashMap, HashSet};
+use std::ops::AddAssign;
+
+/// What welding changed, summed over a scene and its children.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Welded {
+	pub vertices_before: usize,
+	pub vertices_after: usize,
+	/// Triangles that used the same vertex more than once after welding.
+	pub degenerate: usize,
+	/// Triangles that repeated an earlier triangle of the same material, with the same winding.
+	pub duplicate: usize
+}
+
+impl AddAssign for Welded {
+	fn add_assign(&mut self, other: Welded) {
+		self.vertices_before += other.vertices_before;
+		self.vertices_after += other.vertices_after;
+		self.degenerate += other.degenerate;
+		self.duplicate += other.duplicate;
+	}
+}
+
+/// Merges the vertices of each material whose position, normal, and texture coordinate are within `epsilon` of each
+/// other on every axis, in every frame, then removes the triangles that became degenerate or duplicates. Vertices and
+/// triangles not used by any material are dropped.
+pub fn weld(scene: &mut Scene<V2>, epsilon: f32) -> Welded {
+	let mut welded = weld_model(&mut scene.model, epsilon);
+
+	for child in &mut scene.children {
+		welded += weld(child, epsilon);
+	}
+
+	welded
+}
+
+fn weld_model(model: &mut V2, epsilon: f32) -> Welded {
+	let mut welded = Welded::default();
+
+	if model.frames.is_empty() {
+		return welded;
+	}
+
+	welded.vertices_before = model.frames[0].vertices.len();
+
+	// Vertices within epsilon of each other are always in neighbouring cells, as long as the cells are at least as
+	// large as epsilon. A lower bound keeps exact welding from putting every vertex in a cell of its own.
+	let cell_size = epsilon.max(1.0e-6);
+
+	let mut sources = Vec::new();
+	let mut lod_levels = vec![Vec::new(); model.lod_levels.len()];
+	let (frames, old_lod_levels) = (&model.frames, &model.lod_levels);
+
+	for material in &mut model.materials {
+		let first = sources.len();
+		let vertex_offset = material.vertex_offset as usize;
+
+		let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+		let mut remap = Vec::with_capacity(material.vertex_count as usize);
+
+		for vertex in vertex_offset..vertex_offset + material.vertex_count as usize {
+			let position = frames[0].vertices[vertex].position;
+			let cell = [
+				(position.x / cell_size).floor() as i64,
+				(position.y / cell_size).floor() as i64,
+				(position.z / cell_size).floor() as i64
+			];
+
+			let mut found = None;
+
+			'search: for x in -1..=1 {
+				for y in -1..=1 {
+					for z in -1..=1 {
+						let neighbour = [cell[0].saturating_add(x), cell[1].saturating_add(y), cell[2].saturating_add(z)];
+
+						if let Some(candidates) = cells.get(&neighbour) {
+							if let Some(&candidate) = candidates.iter().find(|&&candidate| matches(frames, sources[candidate], vertex, epsilon)) {
+								found = Some(candidate);
+								break 'search;
+							}
+						}
+					}
+				}
+			}
+
+			remap.push(match found {
+				Some(candidate) => candidate - first,
+				None => {
+					cells.entry(cell).or_default().push(sources.len());
+					sources.push(vertex);
+
+					sources.len() - 1 - first
+				}
+			} as u32);
+		}
+
+		for (lod, selection) in material.triangles.iter_mut().enumerate() {
+			let triangles = &mut lod_levels[lod];
+			let offset = triangles.len();
+			let mut seen = HashSet::new();
+
+			for &(a, b, c) in &old_lod_levels[lod][selection.offset as usize..(selection.offset + selection.len) as usize] {
+				let (a, b, c) = (remap[a as usize], remap[b as usize], remap[c as usize]);
+
+				if a == b || b == c || c == a {
+					welded.degenerate += 1;
+					continue;
+				}
+
+				// Rotating the smallest index to the front keeps the winding, so flipped copies are kept as back faces.
+				let key = if a < b && a < c { (a, b, c) } else if b < c { (b, c, a) } else { (c, a, b) };
+
+				if !seen.insert(key) {
+					welded.duplicate += 1;
+					continue;
+				}
+
+				triangles.push((a, b, c));
+			}
+
+			*selection = v2::TriangleSelection { offset: offset as u32, len: (triangles.len() - offset) as u32 };
+		}
+
+		material.vertex_offset = first as u32;
+		material.vertex_count = (sources.len() - first) as u32;
+	}
+
+	for frame in &mut model.frames {
+		frame.vertices = sources.iter().map(|&source| frame.vertices[source]).collect();
+	}
+
+	model.lod_levels = lod_levels;
+	welded.vertices_after = sources.len();
+
+	welded
+}
+
+/// Whether two vertices are within epsilon of each other in every frame.
+fn matches(frames: &[v2::Frame], a: usize, b: usize, epsilon: f32) -> bool {
+	let close = |a: &[f32], b: &[f32]| a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= epsilon);
+
+	frames.iter().all(|frame| {
+		let (a, b) = (frame.vertices[a], frame.vertices[b]);
+
+		close(a.position.as_ref() as &[f32; 3], b.position.as_ref() as &[f32; 3]) &&
+			close(a.normal.as_ref() as &[f32; 3], b.normal.as_ref() as &[f32; 3]) &&
+			close(a.texture.as_ref() as &[f32; 2], b.texture.as_ref() as &[f32; 2])
+	})
+}
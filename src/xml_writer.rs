@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::io::{self, Write};
+
+/// Writes an indented XML document element by element to any `io::Write`, escaping text and attribute values.
+pub struct XmlWriter<W> where W: Write {
+	out: W,
+	/// Open elements, along with whether they have child elements (and so need an indented end tag).
+	stack: Vec<(&'static str, bool)>
+}
+
+impl<W> XmlWriter<W> where W: Write {
+	/// Creates a writer and emits the XML declaration.
+	pub fn new(mut out: W) -> io::Result<Self> {
+		write!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+
+		Ok(XmlWriter { out, stack: Vec::new() })
+	}
+
+	fn indent(&mut self) -> io::Result<()> {
+		if let Some(parent) = self.stack.last_mut() {
+			parent.1 = true;
+		}
+
+		writeln!(self.out)?;
+
+		for _ in 0..self.stack.len() {
+			self.out.write_all(b"  ")?;
+		}
+
+		Ok(())
+	}
+
+	fn open_tag(&mut self, name: &str, attributes: &[(&str, &str)]) -> io::Result<()> {
+		self.indent()?;
+		write!(self.out, "<{}", name)?;
+
+		for &(key, value) in attributes {
+			write!(self.out, " {}=\"", key)?;
+			escape(&mut self.out, value, true)?;
+			self.out.write_all(b"\"")?;
+		}
+
+		Ok(())
+	}
+
+	/// Opens an element, which stays open until the matching call to `end`.
+	pub fn start(&mut self, name: &'static str, attributes: &[(&str, &str)]) -> io::Result<()> {
+		self.open_tag(name, attributes)?;
+		self.out.write_all(b">")?;
+
+		self.stack.push((name, false));
+
+		Ok(())
+	}
+
+	/// Closes the most recently opened element.
+	pub fn end(&mut self) -> io::Result<()> {
+		let (name, has_children) = self.stack.pop().expect("XmlWriter::end called without an open element");
+
+		if has_children {
+			self.indent()?;
+		}
+
+		write!(self.out, "</{}>", name)
+	}
+
+	/// Writes an element without any content.
+	pub fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) -> io::Result<()> {
+		self.open_tag(name, attributes)?;
+		self.out.write_all(b"/>")
+	}
+
+	/// Writes escaped character data into the current element.
+	pub fn text(&mut self, text: &str) -> io::Result<()> {
+		escape(&mut self.out, text, false)
+	}
+
+	/// Writes a whitespace separated list of values into the current element, such as the contents of a `float_array`.
+	/// The values are formatted with `Display` and must not need escaping, which holds for numbers.
+	pub fn list<I, T>(&mut self, values: I) -> io::Result<()> where I: IntoIterator<Item=T>, T: Display {
+		for (index, value) in values.into_iter().enumerate() {
+			if index > 0 {
+				self.out.write_all(b" ")?;
+			}
+
+			write!(self.out, "{}", value)?;
+		}
+
+		Ok(())
+	}
+
+	/// Writes an element that only contains text.
+	pub fn element(&mut self, name: &'static str, attributes: &[(&str, &str)], text: &str) -> io::Result<()> {
+		self.start(name, attributes)?;
+		self.text(text)?;
+		self.end()
+	}
+
+	/// Closes any elements that are still open and returns the underlying writer.
+	pub fn finish(mut self) -> io::Result<W> {
+		while !self.stack.is_empty() {
+			self.end()?;
+		}
+
+		writeln!(self.out)?;
+
+		Ok(self.out)
+	}
+}
+
+/// Escapes markup characters. Characters that XML 1.0 does not allow at all are dropped, and whitespace other than
+/// spaces is written as character references within attributes so that it survives attribute value normalization.
+fn escape<W>(out: &mut W, text: &str, attribute: bool) -> io::Result<()> where W: Write {
+	let mut buffer = [0; 4];
+
+	for c in text.chars() {
+		let replacement = match c {
+			'&' => "&amp;",
+			'<' => "&lt;",
+			'>' => "&gt;",
+			'"' if attribute => "&quot;",
+			'\'' if attribute => "&apos;",
+			'\t' if attribute => "&#x9;",
+			'\n' if attribute => "&#xA;",
+			'\r' => "&#xD;",
+			'\t' | '\n' => c.encode_utf8(&mut buffer),
+			c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => continue,
+			c => c.encode_utf8(&mut buffer)
+		};
+
+		out.write_all(replacement.as_bytes())?;
+	}
+
+	Ok(())
+}
+
+/// Hands out document-unique ids that are valid NCNames, derived from arbitrary names.
+#[derive(Debug, Default)]
+pub struct Ids {
+	used: HashSet<String>
+}
+
+impl Ids {
+	pub fn new() -> Self {
+		Ids::default()
+	}
+
+	/// Returns an id based on `name`, replacing characters that are not allowed and appending a numeric suffix if the
+	/// id was already handed out.
+	pub fn unique(&mut self, name: &str) -> String {
+		let mut base = name.chars()
+			.map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '_' })
+			.collect::<String>();
+
+		if !base.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+			base.insert(0, '_');
+		}
+
+		let mut id = base.clone();
+		let mut suffix = 2;
+
+		while self.used.contains(&id) {
+			id = format!("{}_{}", base, suffix);
+			suffix += 1;
+		}
+
+		self.used.insert(id.clone());
+
+		id
+	}
+}
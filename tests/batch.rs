@@ -0,0 +1,62 @@
+//! Converting directories and glob patterns with the command line tool.
+
+extern crate cem;
+extern crate cemconv;
+
+use cemconv::{convert, validate};
+use std::{env, fs, process};
+use std::io::Cursor;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn converting_a_directory_into_itself_keeps_the_inputs() {
+	let root = env::temp_dir().join(format!("cemconv-batch-{}-inplace", process::id()));
+	let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus");
+
+	let _ = fs::remove_dir_all(&root);
+	fs::create_dir_all(&root).unwrap();
+
+	for name in &["animated.cem", "nested.cem", "triangle.cem"] {
+		fs::copy(corpus.join(name), root.join(name)).unwrap();
+	}
+
+	let output = Command::new(env!("CARGO_BIN_EXE_cemconv"))
+		.args(["convert", "-i"]).arg(&root).args(["-f", "cem", "--recompute-bounds"]).arg(&root)
+		.output().unwrap();
+
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+	let mut names = fs::read_dir(&root).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect::<Vec<_>>();
+	names.sort();
+	assert_eq!(names, vec!["animated.cem", "nested.cem", "triangle.cem"]);
+
+	for name in &names {
+		let scene = convert::read_scene(Cursor::new(fs::read(root.join(name)).unwrap()), "test").unwrap();
+		validate::check(&scene).unwrap();
+	}
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn failed_conversions_leave_existing_outputs_alone() {
+	let root = env::temp_dir().join(format!("cemconv-batch-{}-failed", process::id()));
+
+	let _ = fs::remove_dir_all(&root);
+	fs::create_dir_all(root.join("in")).unwrap();
+	fs::create_dir_all(root.join("out")).unwrap();
+
+	fs::write(root.join("in").join("broken.cem"), "not a model").unwrap();
+	fs::write(root.join("out").join("broken.cem"), "previous output").unwrap();
+
+	let output = Command::new(env!("CARGO_BIN_EXE_cemconv"))
+		.args(["convert", "-i"]).arg(root.join("in")).args(["-f", "cem"]).arg(root.join("out"))
+		.output().unwrap();
+
+	assert!(!output.status.success());
+	assert_eq!(fs::read_to_string(root.join("out").join("broken.cem")).unwrap(), "previous output");
+	assert_eq!(fs::read_dir(root.join("out")).unwrap().count(), 1);
+
+	fs::remove_dir_all(&root).unwrap();
+}
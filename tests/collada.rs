@@ -0,0 +1,90 @@
+//! Importing COLLADA documents, from the layout of the geometry to baking skeletal animations.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+use cem::{V2, Scene};
+use cemconv::convert::{self, ColladaFormat, Format};
+use cgmath::{Point2, Point3, Vector3};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+fn import(name: &str, frame_rate: f32) -> Scene<V2> {
+	let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("collada").join(name)).unwrap();
+
+	convert::read(Cursor::new(bytes), &Format::Collada(ColladaFormat { frame_rate, ..ColladaFormat::default() })).unwrap()
+}
+
+fn positions(model: &V2, frame: usize) -> Vec<Point3<f32>> {
+	model.frames[frame].vertices.iter().map(|vertex| vertex.position).collect()
+}
+
+fn assert_close(actual: &[Point3<f32>], expected: &[Point3<f32>]) {
+	assert_eq!(actual.len(), expected.len());
+
+	for (actual, expected) in actual.iter().zip(expected) {
+		assert!((actual.x - expected.x).abs() < 1e-5 && (actual.y - expected.y).abs() < 1e-5 && (actual.z - expected.z).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+	}
+}
+
+#[test]
+fn skinned_animations_are_baked_into_frames() {
+	// The bind shape lifts the triangle by 1. The first vertex follows the root joint, the second the tip, and the third
+	// is split 3:1 between them. The weights are not normalized, and the tip is raised by 2 over one second.
+	let scene = import("skinned.dae", 2.0);
+
+	assert_eq!(scene.model.frames.len(), 3);
+	assert_eq!(scene.model.lod_levels[0], vec![(0, 1, 2)]);
+
+	assert_close(&positions(&scene.model, 0), &[Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 0.0, 1.0), Point3::new(0.0, 1.0, 1.0)]);
+	assert_close(&positions(&scene.model, 1), &[Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 0.0, 2.0), Point3::new(0.0, 1.0, 1.25)]);
+	assert_close(&positions(&scene.model, 2), &[Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 0.0, 3.0), Point3::new(0.0, 1.0, 1.5)]);
+}
+
+#[test]
+fn accessors_skip_the_offset_and_unnamed_params() {
+	let scene = import("strided.dae", 30.0);
+
+	assert_eq!(scene.model.lod_levels[0], vec![(0, 1, 2)]);
+	assert_close(&positions(&scene.model, 0), &[Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)]);
+}
+
+#[test]
+fn polylists_are_triangulated() {
+	// A unit square followed by a triangle sharing its right edge.
+	let scene = import("polylist.dae", 30.0);
+	let vertices = &scene.model.frames[0].vertices;
+
+	assert_eq!(vertices.len(), 5);
+	assert_eq!(scene.model.lod_levels[0].len(), 3);
+
+	let mut area = 0.0;
+
+	for &(a, b, c) in &scene.model.lod_levels[0] {
+		let (a, b, c) = (vertices[a as usize].position, vertices[b as usize].position, vertices[c as usize].position);
+		let normal = (b - a).cross(c - a);
+
+		assert!(normal.z > 0.0, "triangle {:?} has the wrong winding", (a, b, c));
+		area += normal.z / 2.0;
+	}
+
+	assert!((area - 1.5).abs() < 1e-5);
+
+	let (a, b, c) = scene.model.lod_levels[0][2];
+	assert_close(&[vertices[a as usize].position, vertices[b as usize].position, vertices[c as usize].position], &[Point3::new(1.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0)]);
+}
+
+#[test]
+fn inputs_may_share_an_offset() {
+	// The normals are indexed along with the positions, and the texture coordinates have an offset of their own.
+	let scene = import("shared_offsets.dae", 30.0);
+	let vertices = &scene.model.frames[0].vertices;
+
+	assert_eq!(scene.model.lod_levels[0], vec![(0, 1, 2)]);
+	assert_close(&positions(&scene.model, 0), &[Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)]);
+
+	assert_eq!(vertices.iter().map(|vertex| vertex.normal).collect::<Vec<_>>(), vec![Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)]);
+	assert_eq!(vertices.iter().map(|vertex| vertex.texture).collect::<Vec<_>>(), vec![Point2::new(0.75, 0.0), Point2::new(0.25, 0.5), Point2::new(0.75, 0.0)]);
+}
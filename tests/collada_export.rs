@@ -0,0 +1,64 @@
+//! Writing COLLADA documents, which must stay well-formed whatever the names in the model contain.
+
+extern crate cem;
+extern crate cemconv;
+extern crate xml;
+
+mod common;
+
+use cemconv::convert::{self, Format};
+use common::read;
+use std::collections::HashSet;
+use xml::{Element, Xml};
+
+fn elements(element: &Element) -> Vec<&Element> {
+	let mut elements = vec![element];
+
+	for child in &element.children {
+		if let Xml::ElementNode(ref child) = *child {
+			elements.extend(self::elements(child));
+		}
+	}
+
+	elements
+}
+
+#[test]
+fn names_are_escaped_and_ids_are_unique() {
+	let mut scene = read("animated.cem");
+
+	scene.model.materials[0].texture_name = "a&b \"<pole>\".tga".to_owned();
+	scene.model.materials[1].texture_name = "a<b \"&flag\".tga".to_owned();
+
+	// These are distinct names, but replacing the characters that ids can't contain makes them the same. The last one
+	// is the name of the node holding the model.
+	scene.model.tag_points = vec!["tag&\"1\"".to_owned(), "tag<\"1\"".to_owned(), "Scene_Root".to_owned()];
+
+	for frame in &mut scene.model.frames {
+		let position = frame.tag_points[0];
+		frame.tag_points.push(position);
+	}
+
+	let mut output = Vec::new();
+	convert::write(scene, &mut output, None, &Format::collada()).unwrap();
+
+	let document = String::from_utf8(output).unwrap().parse::<Element>().unwrap();
+	let elements = elements(&document);
+
+	let images = elements.iter().filter(|element| element.name == "image").map(|element| element.get_attribute("name", None).unwrap()).collect::<Vec<_>>();
+	assert_eq!(images, vec!["a&b \"<pole>\".tga", "a<b \"&flag\".tga"]);
+
+	let lights = elements.iter().filter(|element| element.name == "light").map(|element| element.get_attribute("name", None).unwrap()).collect::<Vec<_>>();
+	assert_eq!(lights, vec!["tag&\"1\"", "tag<\"1\"", "Scene_Root"]);
+
+	let mut ids = HashSet::new();
+
+	for id in elements.iter().filter_map(|element| element.get_attribute("id", None)) {
+		assert!(ids.insert(id), "id {:?} is used more than once", id);
+	}
+
+	// Every reference within the document still leads somewhere.
+	for url in elements.iter().filter_map(|element| element.get_attribute("url", None).or_else(|| element.get_attribute("source", None))) {
+		assert!(ids.contains(url.trim_start_matches('#')), "{:?} references a missing id", url);
+	}
+}
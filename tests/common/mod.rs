@@ -0,0 +1,12 @@
+use cem::{V2, Scene};
+use cemconv::convert;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Reads a CEM file from the test corpus.
+pub fn read(name: &str) -> Scene<V2> {
+	let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus").join(name)).unwrap();
+
+	convert::read_scene(Cursor::new(bytes), "test").unwrap()
+}
@@ -0,0 +1,249 @@
+//! Regression tests over the hand-made CEM files in `tests/corpus`. Each `.cem` file is generated from the `.ron`
+//! file next to it, which is the readable source of the model.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+use cem::{V2, Scene};
+use cemconv::convert::{self, ColladaFormat, Format, ObjFormat};
+use cemconv::{bounds, text};
+use cgmath::{Point2, Point3, Vector3, MetricSpace, InnerSpace};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const TOLERANCE: f32 = 1.0e-5;
+
+fn corpus() -> Vec<PathBuf> {
+	let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus");
+
+	let mut files = fs::read_dir(&directory).unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().map(|extension| extension == "cem").unwrap_or(false))
+		.collect::<Vec<_>>();
+
+	files.sort();
+	assert!(!files.is_empty(), "no CEM files found in {}", directory.display());
+
+	files
+}
+
+fn convert(input: &[u8], input_format: &Format, format: &Format) -> Vec<u8> {
+	let mut output = Vec::new();
+
+	convert::convert(Cursor::new(input), &mut output, None, input_format, format).unwrap();
+
+	output
+}
+
+fn read(bytes: &[u8]) -> Scene<V2> {
+	convert::read_scene(Cursor::new(bytes), "test").unwrap()
+}
+
+/// Position, normal, and texture coordinate of every corner of the first level of detail, in material order.
+fn corners(model: &V2, frame: usize) -> Vec<(Point3<f32>, Vector3<f32>, Point2<f32>)> {
+	let vertices = &model.frames[frame].vertices;
+	let mut corners = Vec::new();
+
+	for material in &model.materials {
+		let selection = material.triangles[0];
+
+		for triangle in &model.lod_levels[0][selection.offset as usize..(selection.offset + selection.len) as usize] {
+			for &index in &[triangle.0, triangle.1, triangle.2] {
+				let vertex = vertices[(material.vertex_offset + index) as usize];
+
+				corners.push((vertex.position, vertex.normal, vertex.texture));
+			}
+		}
+	}
+
+	corners
+}
+
+fn assert_geometry_eq(path: &Path, expected: &V2, actual: &V2, frame: usize) {
+	let (expected, actual) = (corners(expected, frame), corners(actual, frame));
+
+	assert_eq!(expected.len(), actual.len(), "{}: corner count of frame {}", path.display(), frame);
+
+	for (index, (a, b)) in expected.iter().zip(actual.iter()).enumerate() {
+		assert!(a.0.distance(b.0) <= TOLERANCE, "{}: position of corner {} in frame {}: {:?} != {:?}", path.display(), index, frame, a.0, b.0);
+		assert!((a.1 - b.1).magnitude() <= TOLERANCE, "{}: normal of corner {} in frame {}: {:?} != {:?}", path.display(), index, frame, a.1, b.1);
+		assert!(a.2.distance(b.2) <= TOLERANCE, "{}: texture coordinate of corner {} in frame {}: {:?} != {:?}", path.display(), index, frame, a.2, b.2);
+	}
+}
+
+fn assert_layout_eq(path: &Path, expected: &Scene<V2>, actual: &Scene<V2>) {
+	assert_eq!(expected.name, actual.name, "{}: scene name", path.display());
+	assert_eq!(expected.model.tag_points, actual.model.tag_points, "{}: tag point names", path.display());
+	assert_eq!(expected.model.frames.len(), actual.model.frames.len(), "{}: frame count", path.display());
+
+	for (index, (a, b)) in expected.model.frames.iter().zip(actual.model.frames.iter()).enumerate() {
+		assert_eq!(a.vertices.len(), b.vertices.len(), "{}: vertex count of frame {}", path.display(), index);
+		assert_eq!(a.tag_points, b.tag_points, "{}: tag points of frame {}", path.display(), index);
+	}
+
+	assert_eq!(expected.children.len(), actual.children.len(), "{}: child count", path.display());
+
+	for (a, b) in expected.children.iter().zip(actual.children.iter()) {
+		assert_layout_eq(path, a, b);
+	}
+}
+
+#[test]
+fn cem_rewrite_is_byte_identical() {
+	for path in corpus() {
+		let original = fs::read(&path).unwrap();
+		let rewritten = convert(&original, &Format::cem(), &Format::cem());
+
+		assert!(original == rewritten, "{}: rewriting changed the file", path.display());
+	}
+}
+
+#[test]
+fn cem_rewrite_leaves_missing_normals_alone() {
+	for path in corpus() {
+		let mut scene = read(&fs::read(&path).unwrap());
+		scene.model.frames[0].vertices[0].normal = Vector3::new(0.0, 0.0, 0.0);
+
+		let mut original = Vec::new();
+		scene.write(&mut original).unwrap();
+
+		let mut rewritten = Vec::new();
+		convert::write(read(&original), &mut rewritten, None, &Format::cem()).unwrap();
+
+		assert!(original == rewritten, "{}: rewriting changed the normals", path.display());
+	}
+}
+
+#[test]
+fn ron_sources_match_corpus() {
+	for path in corpus() {
+		let source = fs::read(path.with_extension("ron")).unwrap();
+
+		let scene = text::read_ron(Cursor::new(source)).unwrap();
+		let mut written = Vec::new();
+		scene.write(&mut written).unwrap();
+
+		assert!(fs::read(&path).unwrap() == written, "{}: out of date with its RON source", path.display());
+	}
+}
+
+#[test]
+fn text_round_trip_is_byte_identical() {
+	for path in corpus() {
+		let original = fs::read(&path).unwrap();
+
+		for format in &[Format::Json, Format::Ron] {
+			let text = convert(&original, &Format::cem(), format);
+			let restored = convert(&text, format, &Format::cem());
+
+			assert!(original == restored, "{}: round trip through {:?} changed the file", path.display(), format);
+		}
+	}
+}
+
+#[test]
+fn obj_round_trip_preserves_geometry() {
+	for path in corpus() {
+		let original = fs::read(&path).unwrap();
+
+		let text = convert(&original, &Format::cem(), &Format::obj());
+		let restored = read(&convert(&text, &Format::obj(), &Format::cem()));
+		let original = read(&original);
+
+		assert_eq!(restored.model.frames.len(), 1, "{}: frame count", path.display());
+		assert_geometry_eq(&path, &original.model, &restored.model, 0);
+	}
+}
+
+#[test]
+fn collada_round_trip_preserves_geometry_and_frames() {
+	for path in corpus() {
+		let original = fs::read(&path).unwrap();
+
+		let dae = convert(&original, &Format::cem(), &Format::collada());
+		let restored = read(&convert(&dae, &Format::collada(), &Format::cem()));
+		let original = read(&original);
+
+		assert_eq!(original.model.frames.len(), restored.model.frames.len(), "{}: frame count", path.display());
+
+		for frame in 0..original.model.frames.len() {
+			assert_geometry_eq(&path, &original.model, &restored.model, frame);
+		}
+	}
+}
+
+#[test]
+fn frame_and_tag_point_layout_survives() {
+	for path in corpus() {
+		let original = fs::read(&path).unwrap();
+		let expected = read(&original);
+
+		assert_layout_eq(&path, &expected, &read(&convert(&original, &Format::cem(), &Format::cem())));
+
+		for format in &[Format::Json, Format::Ron] {
+			let text = convert(&original, &Format::cem(), format);
+
+			assert_layout_eq(&path, &expected, &read(&convert(&text, format, &Format::cem())));
+		}
+	}
+}
+
+#[test]
+fn recomputed_bounds_contain_every_vertex() {
+	fn check(path: &Path, scene: &Scene<V2>) {
+		for (index, frame) in scene.model.frames.iter().enumerate() {
+			let collider = frame.collider;
+
+			for vertex in &frame.vertices {
+				let position = vertex.position;
+
+				assert!(
+					position.x >= collider.aabb.lower.x && position.y >= collider.aabb.lower.y && position.z >= collider.aabb.lower.z &&
+						position.x <= collider.aabb.upper.x && position.y <= collider.aabb.upper.y && position.z <= collider.aabb.upper.z,
+					"{}: frame {} of {:?}: {:?} is outside of the bounding box", path.display(), index, scene.name, position
+				);
+				assert!(position.distance(scene.model.center) <= collider.radius + TOLERANCE, "{}: frame {} of {:?}: {:?} is outside of the radius", path.display(), index, scene.name, position);
+			}
+		}
+
+		for child in &scene.children {
+			check(path, child);
+		}
+	}
+
+	for path in corpus() {
+		let mut scene = read(&fs::read(&path).unwrap());
+
+		bounds::recompute(&mut scene, false);
+		check(&path, &scene);
+
+		let (mut once, mut twice) = (Vec::new(), Vec::new());
+		scene.write(&mut once).unwrap();
+
+		bounds::recompute(&mut scene, false);
+		scene.write(&mut twice).unwrap();
+
+		assert!(once == twice, "{}: recomputing twice changed the bounds", path.display());
+	}
+}
+
+#[test]
+fn debug_bounds_are_ignored_on_import() {
+	for path in corpus() {
+		let original = fs::read(&path).unwrap();
+
+		let formats = [
+			(Format::obj(), Format::Obj(ObjFormat { debug_bounds: true, ..ObjFormat::default() })),
+			(Format::collada(), Format::Collada(ColladaFormat { debug_bounds: true, ..ColladaFormat::default() }))
+		];
+
+		for (plain, with_bounds) in &formats {
+			let expected = convert(&convert(&original, &Format::cem(), plain), plain, &Format::cem());
+			let actual = convert(&convert(&original, &Format::cem(), with_bounds), plain, &Format::cem());
+
+			assert!(expected == actual, "{}: debug bounds in {:?} changed the imported model", path.display(), with_bounds);
+		}
+	}
+}
@@ -0,0 +1,79 @@
+//! Comparing the structure and vertex data of two scenes.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+mod common;
+
+use cemconv::diff::SceneDiff;
+use cgmath::Vector3;
+use common::read;
+
+#[test]
+fn identical_scenes_have_no_differences() {
+	let diff = SceneDiff::new(&read("nested.cem"), &read("nested.cem"), 0.0);
+
+	assert!(diff.is_empty());
+	assert!(diff.children.is_empty());
+}
+
+#[test]
+fn texture_renames_are_reported() {
+	let before = read("animated.cem");
+	let mut after = read("animated.cem");
+	after.model.materials[1].texture_name = "banner.tga".to_owned();
+
+	let diff = SceneDiff::new(&before, &after, 0.0);
+
+	assert!(!diff.is_empty());
+	assert_eq!(diff.materials_changed.len(), 1);
+
+	let material = &diff.materials_changed[0];
+	assert_eq!(material.name, "flag");
+	assert_eq!(material.texture_name, Some(("flag.tga".to_owned(), "banner.tga".to_owned())));
+	assert_eq!((material.texture, material.vertex_count), (None, None));
+	assert!(diff.deviations.is_empty());
+}
+
+#[test]
+fn vertex_deviation_beyond_the_tolerance_is_reported() {
+	let before = read("animated.cem");
+	let mut after = read("animated.cem");
+	after.model.frames[1].vertices[5].position += Vector3::new(0.0, 0.0, 0.01);
+
+	assert!(SceneDiff::new(&before, &after, 0.1).is_empty());
+
+	let diff = SceneDiff::new(&before, &after, 0.001);
+
+	assert_eq!(diff.deviations.len(), 1);
+
+	let deviation = &diff.deviations[0];
+	assert_eq!(deviation.frame, 1);
+	assert!((deviation.position - 0.01).abs() < 1e-6);
+	assert_eq!((deviation.normal, deviation.texture, deviation.tag_point), (0.0, 0.0, 0.0));
+}
+
+#[test]
+fn moved_tag_points_are_matched_by_name() {
+	let before = read("animated.cem");
+	let mut after = read("animated.cem");
+
+	// Reordering the tag points is not a change, moving one is.
+	after.model.tag_points.reverse();
+
+	for frame in &mut after.model.frames {
+		frame.tag_points.reverse();
+	}
+
+	assert!(SceneDiff::new(&before, &after, 0.0).is_empty());
+
+	after.model.frames[2].tag_points[1] += Vector3::new(0.5, 0.0, 0.0);
+
+	let diff = SceneDiff::new(&before, &after, 0.001);
+
+	assert_eq!(diff.deviations.len(), 1);
+	assert_eq!(diff.deviations[0].frame, 2);
+	assert_eq!(diff.deviations[0].tag_point, 0.5);
+	assert_eq!(diff.deviations[0].position, 0.0);
+}
@@ -0,0 +1,99 @@
+//! Extracting materials, levels of detail, frames, and child scenes as standalone models.
+
+extern crate cem;
+extern crate cemconv;
+
+mod common;
+
+use cemconv::{extract, validate};
+use cemconv::extract::{FrameRange, Reference, Selection};
+use common::read;
+
+#[test]
+fn frame_ranges_parse() {
+	assert_eq!("3".parse::<FrameRange>(), Ok(FrameRange { start: 3, end: Some(4) }));
+	assert_eq!("2..5".parse::<FrameRange>(), Ok(FrameRange { start: 2, end: Some(5) }));
+	assert_eq!("4..".parse::<FrameRange>(), Ok(FrameRange { start: 4, end: None }));
+	assert_eq!("..2".parse::<FrameRange>(), Ok(FrameRange { start: 0, end: Some(2) }));
+	assert!("a..b".parse::<FrameRange>().is_err());
+	assert!(usize::MAX.to_string().parse::<FrameRange>().is_err());
+}
+
+#[test]
+fn extracting_a_material_compacts_the_model() {
+	let original = read("animated.cem");
+
+	let selection = Selection {
+		materials: vec![Reference::Name("flag".to_owned())],
+		lod: Some(1),
+		frames: Some(FrameRange { start: 1, end: None }),
+		..Selection::default()
+	};
+
+	let scene = extract::extract(read("animated.cem"), &selection).unwrap();
+	let model = &scene.model;
+
+	validate::check(&scene).unwrap();
+
+	assert_eq!(model.materials.len(), 1);
+	assert_eq!(model.materials[0].vertex_offset, 0);
+	assert_eq!(model.lod_levels, vec![vec![(0, 1, 2)]]);
+	assert_eq!(model.frames.len(), 2);
+	assert_eq!(model.tag_points, original.model.tag_points);
+
+	for (frame, original) in model.frames.iter().zip(original.model.frames[1..].iter()) {
+		assert_eq!(frame.vertices.len(), 3);
+		assert_eq!(frame.vertices[2].position, original.vertices[6].position);
+		assert_eq!(frame.tag_points, original.tag_points);
+	}
+}
+
+#[test]
+fn unused_vertices_are_removed() {
+	// The lowest level of detail of the pole only uses three of its four vertices.
+	let selection = Selection { materials: vec![Reference::Index(0)], lod: Some(1), ..Selection::default() };
+	let scene = extract::extract(read("animated.cem"), &selection).unwrap();
+
+	validate::check(&scene).unwrap();
+	assert_eq!(scene.model.frames[0].vertices.len(), 3);
+}
+
+#[test]
+fn extracting_a_child_scene() {
+	let selection = Selection { child: vec![Reference::Name("turret".to_owned())], ..Selection::default() };
+	let scene = extract::extract(read("nested.cem"), &selection).unwrap();
+
+	validate::check(&scene).unwrap();
+	assert_eq!(scene.name, "turret");
+	assert!(scene.children.is_empty());
+}
+
+#[test]
+fn kept_children_keep_their_bounds() {
+	let original = read("nested.cem");
+	let selection = Selection { frames: Some(FrameRange { start: 0, end: Some(1) }), children: true, ..Selection::default() };
+	let scene = extract::extract(read("nested.cem"), &selection).unwrap();
+
+	validate::check(&scene).unwrap();
+
+	let (before, after) = (&original.children[0].model, &scene.children[0].model);
+	assert_eq!(after.center, before.center);
+	assert_eq!(after.frames[0].collider.aabb.lower, before.frames[0].collider.aabb.lower);
+	assert_eq!(after.frames[0].collider.aabb.upper, before.frames[0].collider.aabb.upper);
+	assert_eq!(after.frames[0].collider.radius, before.frames[0].collider.radius);
+}
+
+#[test]
+fn missing_parts_are_errors() {
+	let selections = [
+		Selection { materials: vec![Reference::Name("shield".to_owned())], ..Selection::default() },
+		Selection { materials: vec![Reference::Index(2)], ..Selection::default() },
+		Selection { lod: Some(2), ..Selection::default() },
+		Selection { frames: Some(FrameRange { start: 2, end: Some(2) }), ..Selection::default() },
+		Selection { child: vec![Reference::Index(0)], ..Selection::default() }
+	];
+
+	for selection in &selections {
+		assert!(extract::extract(read("animated.cem"), selection).is_err(), "{:?} was accepted", selection);
+	}
+}
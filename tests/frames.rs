@@ -0,0 +1,115 @@
+//! Editing the sequence of animation frames.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+mod common;
+
+use cem::{V2, Scene};
+use cemconv::{convert, frames, obj_export, validate};
+use cemconv::convert::Format;
+use cemconv::extract::FrameRange;
+use cemconv::frames::Operation;
+use cgmath::{InnerSpace, Point3};
+use std::io::Cursor;
+use common::read;
+
+/// The position of the tip of the flag in each frame, which tells the frames of the animated model apart.
+fn positions(scene: &Scene<V2>) -> Vec<Point3<f32>> {
+	scene.model.frames.iter().map(|frame| frame.vertices[6].position).collect()
+}
+
+#[test]
+fn reordering() {
+	let original = positions(&read("animated.cem"));
+
+	let cases = [
+		(Operation::Keep(FrameRange { start: 1, end: None }), vec![1, 2]),
+		(Operation::Reverse, vec![2, 1, 0]),
+		(Operation::PingPong, vec![0, 1, 2, 1]),
+		(Operation::DropEvery(2), vec![0, 2])
+	];
+
+	for &(operation, ref order) in &cases {
+		let mut scene = read("animated.cem");
+		frames::apply(&mut scene, operation).unwrap();
+
+		validate::check(&scene).unwrap();
+		assert_eq!(positions(&scene), order.iter().map(|&index| original[index]).collect::<Vec<_>>(), "{:?}", operation);
+	}
+}
+
+#[test]
+fn resampling_interpolates() {
+	let original = read("animated.cem");
+
+	let mut scene = read("animated.cem");
+	frames::apply(&mut scene, Operation::Resample(5)).unwrap();
+
+	validate::check(&scene).unwrap();
+	assert_eq!(scene.model.frames.len(), 5);
+
+	// The original frames land on every other resampled frame, with the halfway points in between.
+	for (index, frame) in scene.model.frames.iter().enumerate() {
+		let (a, b) = (&original.model.frames[index / 2], &original.model.frames[index.div_ceil(2)]);
+
+		for (vertex, (a, b)) in frame.vertices.iter().zip(a.vertices.iter().zip(&b.vertices)) {
+			let halfway = a.position + (b.position - a.position) * 0.5;
+
+			assert!((vertex.position - halfway).magnitude() < 1.0e-5);
+			assert!((vertex.normal.magnitude() - 1.0).abs() < 1.0e-5);
+		}
+
+		for (tag_point, (a, b)) in frame.tag_points.iter().zip(a.tag_points.iter().zip(&b.tag_points)) {
+			assert!((tag_point - a).magnitude() <= (b - a).magnitude() * 0.5 + 1.0e-5);
+		}
+	}
+}
+
+#[test]
+fn invalid_operations_are_errors() {
+	for &operation in &[Operation::DropEvery(1), Operation::Resample(0), Operation::Keep(FrameRange { start: 3, end: None })] {
+		assert!(frames::apply(&mut read("animated.cem"), operation).is_err(), "{:?} was accepted", operation);
+	}
+}
+
+/// The given frame of the animated model, exported as OBJ and imported again, as if it had been edited elsewhere.
+fn pose(frame_index: usize) -> Scene<V2> {
+	let obj = obj_export::convert(&read("animated.cem").model, frame_index, None, false).unwrap();
+
+	convert::read(Cursor::new(obj), &Format::obj()).unwrap()
+}
+
+#[test]
+fn adding_poses() {
+	let original = positions(&read("animated.cem"));
+
+	let mut scene = read("animated.cem");
+	assert_eq!(frames::add(&mut scene, &pose(2), Some(0), false).unwrap(), 1);
+
+	validate::check(&scene).unwrap();
+	assert_eq!(scene.model.frames.len(), 4);
+
+	for (position, expected) in positions(&scene).iter().zip(&[original[2], original[0], original[1], original[2]]) {
+		assert!((position - expected).magnitude() < 1.0e-5);
+	}
+
+	// The poses have no tag points, so those of the neighbouring frame are kept.
+	assert_eq!(scene.model.frames[0].tag_points, scene.model.frames[1].tag_points);
+
+	let mut scene = read("animated.cem");
+	frames::add(&mut scene, &pose(0), Some(2), true).unwrap();
+
+	assert_eq!(scene.model.frames.len(), 3);
+	assert!((positions(&scene)[2] - original[0]).magnitude() < 1.0e-5);
+}
+
+#[test]
+fn poses_must_match_the_triangles() {
+	let mut scene = read("animated.cem");
+
+	assert!(frames::add(&mut scene, &read("triangle.cem"), None, false).is_err());
+	assert!(frames::add(&mut scene, &pose(0), Some(4), false).is_err());
+	assert_eq!(scene.model.frames.len(), 3);
+}
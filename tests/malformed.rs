@@ -0,0 +1,50 @@
+//! Inputs that used to crash the readers. They must be rejected with an error, or converted, but never panic.
+
+extern crate cemconv;
+
+use cemconv::convert::{self, Format};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+fn convert(input: &[u8]) -> io::Result<Vec<u8>> {
+	let mut output = Vec::new();
+
+	convert::convert(Cursor::new(input), &mut output, None, &Format::cem(), &Format::cem()).map(|()| output)
+}
+
+fn read(directory: &str, name: &str) -> Vec<u8> {
+	fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(directory).join(name)).unwrap()
+}
+
+#[test]
+fn truncated_files_are_errors() {
+	for name in &["triangle.cem", "animated.cem", "nested.cem"] {
+		let original = read("corpus", name);
+
+		for len in 0..original.len() {
+			assert!(convert(&original[..len]).is_err(), "{}: truncated to {} bytes was accepted", name, len);
+		}
+	}
+}
+
+#[test]
+fn huge_counts_are_errors() {
+	let original = read("corpus", "triangle.cem");
+
+	// The quantities after the header, set to the largest possible count. The first is the total triangle count, which
+	// the decoder doesn't rely on.
+	for quantity in 1..7 {
+		let mut corrupt = original.clone();
+		let start = 8 + quantity * 4;
+
+		corrupt[start..start + 4].copy_from_slice(&[0xFF; 4]);
+
+		assert!(convert(&corrupt).is_err(), "quantity {} set to u32::MAX was accepted", quantity);
+	}
+}
+
+#[test]
+fn overlapping_selections_recalculate_normals() {
+	convert(&read("malformed", "overlapping_selections.cem")).unwrap();
+}
@@ -0,0 +1,51 @@
+//! Changing the names and textures of materials.
+
+extern crate cem;
+extern crate cemconv;
+
+mod common;
+
+use cemconv::materials;
+use cemconv::extract::Reference;
+use cemconv::materials::{Changes, Mapping};
+use common::read;
+
+#[test]
+fn setting_fields() {
+	let model = &mut read("animated.cem").model;
+
+	let changes = Changes { texture: Some(7), texture_name: Some("flag_red.tga".to_owned()), ..Changes::default() };
+	materials::set(model, &Reference::Name("flag".to_owned()), &changes).unwrap();
+
+	let changes = Changes { name: Some("mast".to_owned()), ..Changes::default() };
+	materials::set(model, &Reference::Index(0), &changes).unwrap();
+
+	let fields = model.materials.iter().map(|material| (&material.name as &str, material.texture, &material.texture_name as &str)).collect::<Vec<_>>();
+	assert_eq!(fields, vec![("mast", 0, "pole.tga"), ("flag", 7, "flag_red.tga")]);
+
+	assert!(materials::set(model, &Reference::Name("pole".to_owned()), &changes).is_err());
+	assert!(materials::set(model, &Reference::Index(2), &changes).is_err());
+}
+
+#[test]
+fn remapping_textures() {
+	let mapping = Mapping::parse("# reskin\n\nBASE.TGA = base_red.tga\nturret.tga=turret_red.tga\n").unwrap();
+
+	assert_eq!(mapping.get("Base.tga"), Some("base_red.tga"));
+	assert_eq!(mapping.get("flag.tga"), None);
+
+	// Child scenes are remapped as well.
+	let mut scene = read("nested.cem");
+	assert_eq!(materials::remap_textures(&mut scene, &mapping), 2);
+	assert_eq!(scene.model.materials[0].texture_name, "base_red.tga");
+	assert_eq!(scene.children[0].model.materials[0].texture_name, "turret_red.tga");
+
+	assert_eq!(materials::remap_textures(&mut scene, &mapping), 0);
+}
+
+#[test]
+fn invalid_mappings_are_errors() {
+	for invalid in &["base.tga", "= base.tga", "a.tga = b.tga\nA.TGA = c.tga"] {
+		assert!(Mapping::parse(invalid).is_err(), "{:?} was accepted", invalid);
+	}
+}
@@ -0,0 +1,68 @@
+//! Merging several models into one scene.
+
+extern crate cem;
+extern crate cemconv;
+
+mod common;
+
+use cemconv::{merge, validate};
+use cemconv::merge::Mode;
+use common::read;
+
+#[test]
+fn concatenating_rebases_materials() {
+	// The triangle has one level of detail and one frame, the animated flag has two levels and three frames.
+	let merged = merge::merge(vec![read("triangle.cem"), read("animated.cem")], Mode::Concatenate).unwrap();
+	let model = &merged.model;
+
+	validate::check(&merged).unwrap();
+
+	assert_eq!(model.materials.len(), 3);
+	assert_eq!(model.lod_levels.iter().map(Vec::len).collect::<Vec<_>>(), vec![1 + 3, 1 + 2]);
+	assert_eq!(model.frames.len(), 3);
+	assert!(model.frames.iter().all(|frame| frame.vertices.len() == 3 + 7));
+	assert_eq!(model.tag_points, vec!["top".to_owned(), "light_255_255_255_1_1_1".to_owned()]);
+
+	// The triangle reuses its only level of detail, and the flag is moved past it.
+	let triangle = &model.materials[0];
+	assert_eq!((triangle.triangles[1].offset, triangle.triangles[1].len), (0, 1));
+
+	let flag = &model.materials[2];
+	assert_eq!(flag.vertex_offset, 3 + 4);
+	assert_eq!((flag.triangles[0].offset, flag.triangles[0].len), (1 + 2, 1));
+	assert_eq!((flag.triangles[1].offset, flag.triangles[1].len), (1 + 1, 1));
+
+	// The single frame of the triangle is held while the flag moves.
+	let animated = read("animated.cem");
+
+	for (frame, original) in model.frames.iter().zip(animated.model.frames.iter()) {
+		assert_eq!(frame.vertices[0].position, model.frames[0].vertices[0].position);
+		assert_eq!(frame.vertices[3 + 6].position, original.vertices[6].position);
+		assert_eq!(frame.tag_points, original.tag_points);
+	}
+}
+
+#[test]
+fn children_keep_their_models() {
+	let merged = merge::merge(vec![read("animated.cem"), read("nested.cem")], Mode::Children).unwrap();
+
+	validate::check(&merged).unwrap();
+
+	assert_eq!(merged.model.materials.len(), 2);
+	assert_eq!(merged.children.len(), 1);
+	assert_eq!(merged.children[0].children.len(), 1);
+
+	// Only the root is recomputed, the stored bounds of the turret are kept.
+	assert_eq!(merged.children[0].children[0].model.frames[0].collider.radius, 0.5);
+}
+
+#[test]
+fn concatenating_keeps_children() {
+	let merged = merge::merge(vec![read("animated.cem"), read("nested.cem")], Mode::Concatenate).unwrap();
+
+	validate::check(&merged).unwrap();
+
+	assert_eq!(merged.model.materials.len(), 3);
+	assert_eq!(merged.children.len(), 1);
+	assert_eq!(merged.children[0].model.frames[0].collider.radius, 0.5);
+}
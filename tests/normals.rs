@@ -0,0 +1,87 @@
+//! Recalculating normals, and splitting vertices along the hard edges that the mode asks for.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+use cem::V2;
+use cemconv::normals::{self, NormalMode};
+use cemconv::obj_import;
+use cgmath::{Vector3, InnerSpace};
+
+/// Two triangles sharing an edge along the X axis, folded by 30 degrees.
+fn hinge() -> V2 {
+	let (sin, cos) = 30.0f32.to_radians().sin_cos();
+
+	obj_import::convert(&format!("v 0 0 0\nv 1 0 0\nv 0.5 1 0\nv 0.5 {} {}\nf 1 2 3\nf 2 1 4\n", -cos, sin), false).unwrap()
+}
+
+fn recalculate(mode: NormalMode) -> V2 {
+	let mut model = hinge();
+	normals::recalculate(&mut model, mode);
+
+	model
+}
+
+fn face_normals(model: &V2) -> Vec<Vector3<f32>> {
+	let vertices = &model.frames[0].vertices;
+
+	model.lod_levels[0].iter().map(|&(a, b, c)| {
+		let (a, b, c) = (vertices[a as usize].position, vertices[b as usize].position, vertices[c as usize].position);
+
+		(b - a).cross(c - a).normalize()
+	}).collect()
+}
+
+/// Checks that every corner of every face has the normal of the face.
+fn assert_hard(model: &V2) {
+	let vertices = &model.frames[0].vertices;
+
+	assert_eq!(vertices.len(), 6);
+
+	for (&(a, b, c), normal) in model.lod_levels[0].iter().zip(face_normals(model)) {
+		for &vertex in &[a, b, c] {
+			assert!(vertices[vertex as usize].normal.dot(normal) > 0.9999, "{:?} is not the face normal {:?}", vertices[vertex as usize].normal, normal);
+		}
+	}
+}
+
+/// Checks that the corners on the shared edge have a single normal halfway between the faces.
+fn assert_smooth(model: &V2) {
+	let vertices = &model.frames[0].vertices;
+	let faces = face_normals(model);
+	let halfway = (faces[0] + faces[1]).normalize();
+
+	assert_eq!(vertices.len(), 4);
+
+	let (a, b, _) = model.lod_levels[0][0];
+
+	for &vertex in &[a, b] {
+		assert!(vertices[vertex as usize].normal.dot(halfway) > 0.9999, "{:?} is not halfway between the faces", vertices[vertex as usize].normal);
+	}
+}
+
+#[test]
+fn smooth_normals_are_shared() {
+	assert_smooth(&recalculate(NormalMode::Smooth));
+}
+
+#[test]
+fn flat_normals_split_every_edge() {
+	assert_hard(&recalculate(NormalMode::Flat));
+}
+
+#[test]
+fn edges_sharper_than_the_angle_are_split() {
+	assert_smooth(&recalculate(NormalMode::Angle(45.0)));
+	assert_hard(&recalculate(NormalMode::Angle(20.0)));
+}
+
+#[test]
+fn coplanar_faces_share_flat_normals() {
+	let mut model = obj_import::convert("v 0 0 0\nv 1 0 0\nv 0.5 1 0\nv 0.5 -1 0\nf 1 2 3\nf 2 1 4\n", false).unwrap();
+	normals::recalculate(&mut model, NormalMode::Flat);
+
+	assert_eq!(model.frames[0].vertices.len(), 4);
+	assert!(model.frames[0].vertices.iter().all(|vertex| vertex.normal.dot(Vector3::new(0.0, -1.0, 0.0)) > 0.9999));
+}
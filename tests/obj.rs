@@ -0,0 +1,116 @@
+//! Importing OBJ files, in particular the triangulation of faces with more than three corners.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+use cem::V2;
+use cemconv::obj_import;
+use cgmath::{Point2, Vector3, InnerSpace};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The positions of the vertices of every triangle in the plane of the face. OBJ files are Y up, so a face in the XY
+/// plane of the file ends up in the XZ plane of the model.
+fn triangles(model: &V2) -> Vec<[Point2<f32>; 3]> {
+	let vertices = &model.frames[0].vertices;
+	let point = |index: u32| Point2::new(vertices[index as usize].position.x, vertices[index as usize].position.z);
+
+	model.lod_levels[0].iter().map(|&(a, b, c)| [point(a), point(b), point(c)]).collect()
+}
+
+/// Twice the signed area of the triangle, positive if counter-clockwise.
+fn cross(a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> f32 {
+	(b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+fn polygon_area(polygon: &[Point2<f32>]) -> f32 {
+	let origin = polygon[0];
+
+	(1..polygon.len() - 1).map(|i| cross(origin, polygon[i], polygon[i + 1])).sum::<f32>() / 2.0
+}
+
+fn contains(polygon: &[Point2<f32>], point: Point2<f32>) -> bool {
+	let mut inside = false;
+
+	for (index, &a) in polygon.iter().enumerate() {
+		let b = polygon[(index + 1) % polygon.len()];
+
+		if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+			inside = !inside;
+		}
+	}
+
+	inside
+}
+
+/// Imports a single face with the given corners, and checks that the triangles exactly cover it with the same winding.
+fn check_face(corners: &[(f32, f32)]) {
+	let source = corners.iter().map(|&(x, y)| format!("v {} {} 0\n", x, y)).collect::<String>()
+		+ &format!("f {}\n", (1..corners.len() + 1).map(|index| index.to_string()).collect::<Vec<_>>().join(" "));
+
+	let model = obj_import::convert(&source, false).unwrap();
+	let triangles = triangles(&model);
+
+	let polygon = corners.iter().map(|&(x, y)| Point2::new(x, y)).collect::<Vec<_>>();
+	let area = polygon_area(&polygon);
+
+	assert_eq!(triangles.len(), corners.len() - 2);
+
+	for &[a, b, c] in &triangles {
+		let centroid = Point2::new((a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0);
+
+		assert!(contains(&polygon, centroid), "triangle {:?} is outside of the face", [a, b, c]);
+		assert!(cross(a, b, c) * area > 0.0, "triangle {:?} has the wrong winding", [a, b, c]);
+	}
+
+	let covered = triangles.iter().map(|&[a, b, c]| cross(a, b, c) / 2.0).sum::<f32>();
+	assert!((covered - area).abs() < 1e-5, "triangles cover {} of a face with an area of {}", covered, area);
+
+	// A counter-clockwise face in the XY plane of the file faces +Z, which becomes -Y in the model.
+	let normal = Vector3::new(0.0, -area.signum(), 0.0);
+
+	for &(a, b, c) in &model.lod_levels[0] {
+		let vertices = &model.frames[0].vertices;
+		let (a, b, c) = (vertices[a as usize].position, vertices[b as usize].position, vertices[c as usize].position);
+
+		assert!((b - a).cross(c - a).dot(normal) > 0.0);
+	}
+}
+
+#[test]
+fn quads_are_split_in_two() {
+	check_face(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+	check_face(&[(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)]);
+}
+
+#[test]
+fn concave_faces_are_not_fanned() {
+	// A fan around the first corner would cover the notch between the first, second, and third corners.
+	check_face(&[(4.0, 4.0), (2.0, 1.0), (0.0, 4.0), (0.0, 0.0), (4.0, 0.0)]);
+	check_face(&[(4.0, 0.0), (0.0, 0.0), (0.0, 4.0), (2.0, 1.0), (4.0, 4.0)]);
+}
+
+#[test]
+fn lines_and_points_are_skipped_with_a_warning() {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_cemconv"))
+		.args(["convert", "-g", "obj", "-f", "cem"])
+		.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+		.spawn().unwrap();
+
+	child.stdin.take().unwrap().write_all(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nl 1 2\nl 2 3\np 1\nf 1 2 3\n").unwrap();
+
+	let output = child.wait_with_output().unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success(), "{}", stderr);
+	assert!(stderr.contains("skipped 2 lines and 1 points"), "{}", stderr);
+}
+
+#[test]
+fn faces_are_the_only_geometry() {
+	let model = obj_import::convert("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nl 1 4\np 4\nf 1 2 3\n", false).unwrap();
+
+	assert_eq!(model.lod_levels[0], vec![(0, 1, 2)]);
+	assert_eq!(model.frames[0].vertices.len(), 3);
+}
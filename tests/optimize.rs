@@ -0,0 +1,180 @@
+//! Reordering triangles and vertices for the vertex cache, which must not change how the model looks.
+
+extern crate cem;
+extern crate cemconv;
+
+use cem::{V2, Scene};
+use cemconv::{obj_import, optimize, text, validate};
+
+/// A grid of quads on a bumpy surface, with the triangles in a scrambled order.
+fn grid(size: u32) -> Scene<V2> {
+	let mut obj = String::new();
+
+	for y in 0..size + 1 {
+		for x in 0..size + 1 {
+			obj.push_str(&format!("v {} {} {}\n", x, y, ((x * y) % 3) as f32 * 0.1));
+		}
+	}
+
+	let mut faces = Vec::new();
+
+	for y in 0..size {
+		for x in 0..size {
+			let a = y * (size + 1) + x + 1;
+
+			faces.push(format!("f {} {} {}\n", a, a + 1, a + size + 2));
+			faces.push(format!("f {} {} {}\n", a, a + size + 2, a + size + 1));
+		}
+	}
+
+	// A fixed stride through the faces scrambles them without needing a random number generator.
+	let len = faces.len();
+
+	for index in 0..len {
+		obj.push_str(&faces[(index * 7919) % len]);
+	}
+
+	Scene::root(obj_import::convert(&obj, false).unwrap())
+}
+
+/// Every triangle as the bits of its corner positions, with the rotation normalised, in sorted order.
+fn triangles(model: &V2) -> Vec<[[u32; 3]; 3]> {
+	let vertices = &model.frames[0].vertices;
+
+	let mut triangles = model.lod_levels[0].iter().map(|&(a, b, c)| {
+		let corner = |index: u32| {
+			let position = vertices[index as usize].position;
+			[position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]
+		};
+
+		let corners = [corner(a), corner(b), corner(c)];
+		let first = (0..3).min_by_key(|&index| corners[index]).unwrap();
+
+		[corners[first], corners[(first + 1) % 3], corners[(first + 2) % 3]]
+	}).collect::<Vec<_>>();
+
+	triangles.sort();
+	triangles
+}
+
+/// Number of vertices transformed with a FIFO cache of 16 vertices.
+fn misses(model: &V2) -> usize {
+	let mut cache: Vec<u32> = Vec::new();
+	let mut misses = 0;
+
+	for &(a, b, c) in &model.lod_levels[0] {
+		for &vertex in &[a, b, c] {
+			if !cache.contains(&vertex) {
+				misses += 1;
+				cache.push(vertex);
+
+				if cache.len() > 16 {
+					cache.remove(0);
+				}
+			}
+		}
+	}
+
+	misses
+}
+
+#[test]
+fn optimizing_keeps_the_triangles() {
+	let mut scene = grid(20);
+	let before = triangles(&scene.model);
+
+	optimize::optimize(&mut scene, false);
+
+	assert_eq!(before, triangles(&scene.model));
+}
+
+#[test]
+fn optimizing_reduces_cache_misses() {
+	let mut scene = grid(20);
+	let before = misses(&scene.model);
+
+	optimize::optimize(&mut scene, false);
+
+	let after = misses(&scene.model);
+
+	assert!(after * 2 < before, "cache misses only went from {} to {}", before, after);
+}
+
+#[test]
+fn vertices_are_in_order_of_first_use() {
+	let mut scene = grid(20);
+
+	optimize::optimize(&mut scene, false);
+
+	let mut next = 0;
+
+	for &(a, b, c) in &scene.model.lod_levels[0] {
+		for &vertex in &[a, b, c] {
+			assert!(vertex <= next, "vertex {} is used before vertex {}", vertex, next);
+
+			if vertex == next {
+				next += 1;
+			}
+		}
+	}
+
+	assert_eq!(next as usize, scene.model.frames[0].vertices.len());
+}
+
+/// Two materials sharing vertices and a triangle, which validation only warns about.
+const OVERLAPPING: &str = r#"(
+	version: "2.0",
+	scene: (
+		name: "overlapping",
+		center: (0.0, 0.0, 0.0),
+		materials: [
+			(name: "a", texture: 0, texture_name: "a.tga", vertex_offset: 0, vertex_count: 6, triangles: [(0, 2)]),
+			(name: "b", texture: 1, texture_name: "b.tga", vertex_offset: 0, vertex_count: 3, triangles: [(1, 1)]),
+		],
+		lod_levels: [
+			[(3, 4, 5), (0, 1, 2)],
+		],
+		tag_points: [],
+		frames: [
+			(
+				tag_points: [],
+				vertices: [
+					(position: (0.0, 0.0, 0.0), normal: (0.0, -1.0, 0.0), texture: (0.0, 0.0)),
+					(position: (1.0, 0.0, 0.0), normal: (0.0, -1.0, 0.0), texture: (0.0, 0.0)),
+					(position: (0.0, 0.0, 1.0), normal: (0.0, -1.0, 0.0), texture: (0.0, 0.0)),
+					(position: (2.0, 0.0, 0.0), normal: (0.0, -1.0, 0.0), texture: (0.0, 0.0)),
+					(position: (3.0, 0.0, 0.0), normal: (0.0, -1.0, 0.0), texture: (0.0, 0.0)),
+					(position: (2.0, 0.0, 1.0), normal: (0.0, -1.0, 0.0), texture: (0.0, 0.0)),
+				],
+			),
+		],
+	),
+)"#;
+
+/// The corner positions of the triangles of each material, in order.
+fn material_triangles(model: &V2) -> Vec<Vec<[[f32; 3]; 3]>> {
+	model.materials.iter().map(|material| {
+		let selection = material.triangles[0];
+
+		let mut triangles = model.lod_levels[0][selection.offset as usize..(selection.offset + selection.len) as usize].iter().map(|&(a, b, c)| {
+			let corner = |index: u32| model.frames[0].vertices[(material.vertex_offset + index) as usize].position.into();
+			[corner(a), corner(b), corner(c)]
+		}).collect::<Vec<[[f32; 3]; 3]>>();
+
+		triangles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		triangles
+	}).collect()
+}
+
+#[test]
+fn overlapping_materials_are_optimized_separately() {
+	let mut scene = text::read_ron(OVERLAPPING.as_bytes()).unwrap();
+	validate::check(&scene).unwrap();
+
+	let before = material_triangles(&scene.model);
+
+	optimize::optimize(&mut scene, false);
+
+	validate::check(&scene).unwrap();
+	assert_eq!(before, material_triangles(&scene.model));
+}
@@ -0,0 +1,77 @@
+//! Editing tag points, and the names of light emitting tag points.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+mod common;
+
+use cemconv::{tags, validate};
+use cemconv::tags::{FrameSelection, Light};
+use cgmath::Point3;
+use common::read;
+
+#[test]
+fn light_names() {
+	let light = Light { color: tags::parse_color("#ffc840").unwrap(), unknown: [1, 1, 1] };
+
+	assert_eq!(light.to_string(), "light_255_200_64_1_1_1");
+	assert_eq!("light_255_200_64_1_1_1".parse::<Light>(), Ok(light));
+	assert_eq!(tags::parse_color("255, 200, 64"), Ok([255, 200, 64]));
+
+	for invalid in &["#ffc84", "256,0,0", "1,2", "red"] {
+		assert!(tags::parse_color(invalid).is_err(), "{:?} was accepted", invalid);
+	}
+
+	assert!("light_1_2_3".parse::<Light>().is_err());
+	assert!("lamp_1_2_3_4_5_6".parse::<Light>().is_err());
+}
+
+#[test]
+fn editing_keeps_frames_consistent() {
+	let mut scene = read("animated.cem");
+
+	{
+		let model = &mut scene.model;
+
+		tags::add(model, "muzzle", Point3::new(1.0, 2.0, 3.0), FrameSelection::All).unwrap();
+		tags::move_to(model, "muzzle", Point3::new(0.0, 0.0, 0.0), FrameSelection::Frame(1)).unwrap();
+		tags::remove(model, "top").unwrap();
+		tags::rename(model, "muzzle", "gun").unwrap();
+
+		assert_eq!(model.tag_points, vec!["light_255_255_255_1_1_1".to_owned(), "gun".to_owned()]);
+
+		let positions = model.frames.iter().map(|frame| frame.tag_points[1]).collect::<Vec<_>>();
+		assert_eq!(positions, vec![Point3::new(1.0, 2.0, 3.0), Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0)]);
+	}
+
+	validate::check(&scene).unwrap();
+}
+
+#[test]
+fn adding_to_one_frame_places_the_others_at_the_origin() {
+	let mut scene = read("animated.cem");
+
+	tags::add(&mut scene.model, "muzzle", Point3::new(1.0, 2.0, 3.0), FrameSelection::Frame(1)).unwrap();
+
+	let positions = scene.model.frames.iter().map(|frame| frame.tag_points[2]).collect::<Vec<_>>();
+	assert_eq!(positions, vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0), Point3::new(0.0, 0.0, 0.0)]);
+
+	validate::check(&scene).unwrap();
+}
+
+#[test]
+fn invalid_edits_are_errors() {
+	let model = &mut read("animated.cem").model;
+	let origin = Point3::new(0.0, 0.0, 0.0);
+
+	assert!(tags::add(model, "top", origin, FrameSelection::All).is_err());
+	assert!(tags::add(model, "muzzle", origin, FrameSelection::Frame(3)).is_err());
+	assert!(tags::rename(model, "top", "light_255_255_255_1_1_1").is_err());
+	assert!(tags::remove(model, "muzzle").is_err());
+	assert!(tags::move_to(model, "muzzle", origin, FrameSelection::All).is_err());
+	assert!(tags::move_to(model, "top", origin, FrameSelection::Frame(3)).is_err());
+
+	assert_eq!(model.tag_points.len(), 2);
+	assert!(model.frames.iter().all(|frame| frame.tag_points.len() == 2));
+}
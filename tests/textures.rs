@@ -0,0 +1,100 @@
+//! Finding the textures of exported models and placing them next to the output.
+
+extern crate cem;
+extern crate cemconv;
+
+mod common;
+
+use cemconv::{convert, textures};
+use cemconv::convert::{Format, ObjFormat};
+use cemconv::textures::TextureDir;
+use std::{env, fs, process};
+use std::io;
+use std::path::PathBuf;
+use common::read;
+
+/// A fresh directory for a test, with a texture directory in it that mixes upper and lower case.
+fn workspace(test: &str) -> PathBuf {
+	let root = env::temp_dir().join(format!("cemconv-textures-{}-{}", process::id(), test));
+
+	let _ = fs::remove_dir_all(&root);
+	fs::create_dir_all(root.join("data").join("Textures").join("units")).unwrap();
+	fs::create_dir_all(root.join("data").join("Textures").join("props")).unwrap();
+	fs::create_dir_all(root.join("out")).unwrap();
+
+	fs::write(root.join("data").join("Textures").join("units").join("POLE.TGA"), "pole").unwrap();
+	fs::write(root.join("data").join("Textures").join("units").join("flag.tga"), "flag").unwrap();
+	fs::write(root.join("data").join("Textures").join("props").join("flag.tga"), "other flag").unwrap();
+
+	root
+}
+
+#[test]
+fn textures_are_found_and_copied() {
+	let root = workspace("copy");
+	let texture_dir = TextureDir::open(root.join("data"), false).unwrap();
+
+	let mut scene = read("nested.cem");
+	scene.model.materials[0].texture_name = "textures\\units\\pole.tga".to_owned();
+	scene.children[0].model.materials[0].texture_name = "Textures/props/flag.tga".to_owned();
+
+	let mut flag = read("animated.cem");
+	flag.model.materials[0].texture_name = "missing.tga".to_owned();
+	flag.model.materials[1].texture_name = "TEXTURES/UNITS/FLAG.TGA".to_owned();
+	scene.children.push(flag);
+
+	textures::package(&mut scene, &texture_dir, &root.join("out")).unwrap();
+
+	// Different textures with the same file name don't overwrite each other, and missing textures are left as they were.
+	let names = [&scene.model.materials[0], &scene.children[0].model.materials[0], &scene.children[1].model.materials[0], &scene.children[1].model.materials[1]]
+		.iter().map(|material| material.texture_name.clone()).collect::<Vec<_>>();
+
+	assert_eq!(names, vec!["POLE.TGA", "flag.tga", "missing.tga", "flag_2.tga"]);
+	assert_eq!(fs::read_to_string(root.join("out").join("POLE.TGA")).unwrap(), "pole");
+	assert_eq!(fs::read_to_string(root.join("out").join("flag.tga")).unwrap(), "other flag");
+	assert_eq!(fs::read_to_string(root.join("out").join("flag_2.tga")).unwrap(), "flag");
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn models_of_a_batch_share_the_output_directory() {
+	let root = workspace("batch");
+	let texture_dir = TextureDir::open(root.join("data"), false).unwrap();
+
+	let mut first = read("animated.cem");
+	first.model.materials[1].texture_name = "Textures/units/flag.tga".to_owned();
+
+	let mut second = read("animated.cem");
+	second.model.materials[1].texture_name = "Textures/props/flag.tga".to_owned();
+
+	// Each file of a batch gets its own clone of the texture directory.
+	textures::package(&mut first, &texture_dir.clone(), &root.join("out")).unwrap();
+	textures::package(&mut second, &texture_dir.clone(), &root.join("out")).unwrap();
+
+	assert_eq!(first.model.materials[1].texture_name, "flag.tga");
+	assert_eq!(second.model.materials[1].texture_name, "flag_2.tga");
+	assert_eq!(fs::read_to_string(root.join("out").join("flag.tga")).unwrap(), "flag");
+	assert_eq!(fs::read_to_string(root.join("out").join("flag_2.tga")).unwrap(), "other flag");
+
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn obj_export_refers_to_the_copies() {
+	let root = workspace("obj");
+	let output = root.join("out").join("flag.obj");
+	let format = Format::Obj(ObjFormat { textures: Some(TextureDir::open(root.join("data"), false).unwrap()), ..ObjFormat::default() });
+
+	convert::write(read("animated.cem"), io::sink(), Some(&output), &format).unwrap();
+
+	let mtl = fs::read_to_string(output.with_extension("mtl")).unwrap();
+
+	assert!(mtl.contains("map_Kd POLE.TGA"), "{}", mtl);
+	assert!(mtl.contains("map_Kd flag.tga"), "{}", mtl);
+	assert!(root.join("out").join("POLE.TGA").is_file());
+
+	assert!(convert::write(read("animated.cem"), io::sink(), None, &format).is_err());
+
+	fs::remove_dir_all(&root).unwrap();
+}
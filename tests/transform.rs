@@ -0,0 +1,136 @@
+//! Scaling, mirroring, rotating, and translating models when writing CEM files.
+
+extern crate cem;
+extern crate cemconv;
+extern crate cgmath;
+
+mod common;
+
+use cem::{V2, Scene};
+use cemconv::convert::{self, CemFormat, Format};
+use cemconv::transform::{self, Axis, Rotation};
+use cgmath::{Point3, Vector3, Matrix4, Deg, MetricSpace, InnerSpace, Zero};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use common::read;
+
+const TOLERANCE: f32 = 1.0e-5;
+
+fn animated() -> Vec<u8> {
+	fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus").join("animated.cem")).unwrap()
+}
+
+fn transformed(matrix: Matrix4<f32>) -> Scene<V2> {
+	let format = Format::Cem(CemFormat { transform: Some(matrix), ..CemFormat::default() });
+	let mut output = Vec::new();
+
+	convert::convert(Cursor::new(animated()), &mut output, None, &format, &format).unwrap();
+	convert::read_scene(Cursor::new(output), "test").unwrap()
+}
+
+/// Whether each triangle faces the same way as the normals of its corners.
+fn faces_along_normals(model: &V2) -> bool {
+	model.materials.iter().all(|material| {
+		let selection = material.triangles[0];
+
+		model.lod_levels[0][selection.offset as usize..(selection.offset + selection.len) as usize].iter().all(|triangle| {
+			model.frames.iter().all(|frame| {
+				let vertex = |index: u32| frame.vertices[(material.vertex_offset + index) as usize];
+				let (a, b, c) = (vertex(triangle.0), vertex(triangle.1), vertex(triangle.2));
+
+				(b.position - a.position).cross(c.position - a.position).dot(a.normal + b.normal + c.normal) > 0.0
+			})
+		})
+	})
+}
+
+#[test]
+fn mirroring_flips_winding() {
+	let original = convert::read_scene(Cursor::new(animated()), "test").unwrap();
+	assert!(faces_along_normals(&original.model));
+
+	for &axis in &[Axis::X, Axis::Y, Axis::Z] {
+		let matrix = transform::matrix(Vector3::new(1.0, 1.0, 1.0), &[axis], &[], Vector3::zero());
+
+		assert!(faces_along_normals(&transformed(matrix).model), "mirroring along {:?} turned the triangles inside out", axis);
+	}
+}
+
+#[test]
+fn operations_apply_in_order() {
+	let rotation = Rotation { axis: Axis::Z, angle: Deg(90.0) };
+	let matrix = transform::matrix(Vector3::new(2.0, 2.0, 2.0), &[Axis::X], &[rotation], Vector3::new(1.0, 0.0, 0.0));
+	let scene = transformed(matrix);
+
+	// The light of the first frame at (0.5, 0, 1.75) is scaled to (1, 0, 3.5), mirrored to (-1, 0, 3.5), rotated to
+	// (0, -1, 3.5), and moved to (1, -1, 3.5).
+	let light = scene.model.frames[0].tag_points[1];
+	assert!(light.distance(Point3::new(1.0, -1.0, 3.5)) < TOLERANCE, "light is at {:?}", light);
+
+	// Normals pointing along -Y end up along +X.
+	let normal = scene.model.frames[0].vertices[0].normal;
+	assert!((normal - Vector3::unit_x()).magnitude() < TOLERANCE, "normal is {:?}", normal);
+}
+
+#[test]
+fn bounds_follow_the_model() {
+	let matrix = transform::matrix(Vector3::new(1.0, 1.0, 3.0), &[], &[], Vector3::new(0.0, 0.0, -10.0));
+	let scene = transformed(matrix);
+
+	for frame in &scene.model.frames {
+		assert!((frame.collider.aabb.lower.z + 10.0).abs() < TOLERANCE && (frame.collider.aabb.upper.z + 4.0).abs() < TOLERANCE, "bounds are {:?}", frame.collider.aabb);
+	}
+
+	assert!((scene.model.center.z + 7.0).abs() < TOLERANCE, "center is {:?}", scene.model.center);
+}
+
+#[test]
+fn non_uniform_scale_keeps_normals_perpendicular() {
+	let matrix = transform::matrix(Vector3::new(5.0, 1.0, 0.5), &[], &[Rotation { axis: Axis::X, angle: Deg(30.0) }], Vector3::zero());
+	let scene = transformed(matrix);
+	let model = &scene.model;
+
+	for material in &model.materials {
+		let selection = material.triangles[0];
+
+		for triangle in &model.lod_levels[0][selection.offset as usize..(selection.offset + selection.len) as usize] {
+			for frame in &model.frames {
+				let vertex = |index: u32| frame.vertices[(material.vertex_offset + index) as usize];
+				let (a, b, c) = (vertex(triangle.0), vertex(triangle.1), vertex(triangle.2));
+
+				// The flag bends between frames, so only the flat pole has normals exactly perpendicular to its faces.
+				if material.name == "pole" {
+					assert!((b.position - a.position).dot(a.normal).abs() < TOLERANCE);
+					assert!((c.position - a.position).dot(a.normal).abs() < TOLERANCE);
+				}
+
+				assert!((a.normal.magnitude() - 1.0).abs() < TOLERANCE);
+			}
+		}
+	}
+}
+
+#[test]
+fn child_transforms_follow_the_model() {
+	let original = read("nested.cem");
+	let mut scene = read("nested.cem");
+
+	let matrix = transform::matrix(Vector3::new(2.0, 2.0, 2.0), &[], &[Rotation { axis: Axis::Z, angle: Deg(90.0) }], Vector3::new(0.0, 0.0, 1.0));
+	transform::apply(&mut scene, matrix);
+
+	let (before, after) = (&original.children[0].model.frames[0], &scene.children[0].model.frames[0]);
+
+	// Placed by its transform, every vertex of the turret ends up where the transformed parent puts it.
+	for (vertex_before, vertex_after) in before.vertices.iter().zip(after.vertices.iter()) {
+		let expected = Point3::from_homogeneous(matrix * before.transform * vertex_before.position.to_homogeneous());
+		let placed = Point3::from_homogeneous(after.transform * vertex_after.position.to_homogeneous());
+
+		assert!(placed.distance(expected) < TOLERANCE, "vertex is placed at {:?}, expected {:?}", placed, expected);
+	}
+
+	// The turret stays on the attachment point of the base.
+	let attach = scene.model.frames[0].tag_points[0];
+	let origin = Point3::from_homogeneous(after.transform * matrix * Point3::new(0.0, 0.0, 0.0).to_homogeneous());
+	assert!(origin.distance(attach) < TOLERANCE, "turret origin is at {:?}, attachment point at {:?}", origin, attach);
+}
@@ -0,0 +1,70 @@
+//! Welding vertices and cleaning up triangles when writing CEM files.
+
+extern crate cem;
+extern crate cemconv;
+
+use cem::{V2, Scene};
+use cemconv::convert;
+use cemconv::normals::NormalMode;
+use cemconv::obj_import;
+use cemconv::weld;
+
+/// A quad where each triangle has its own copy of the shared corners, a repeated triangle, and a sliver whose two
+/// corners are within a hundredth of each other.
+const QUAD: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 0 0
+v 1 1 0
+v 0 1 0
+v 0.005 1 0
+vn 0 0 1
+vt 0 0
+f 1/1/1 2/1/1 3/1/1
+f 4/1/1 5/1/1 6/1/1
+f 4/1/1 5/1/1 6/1/1
+f 4/1/1 6/1/1 7/1/1
+";
+
+fn quad() -> Scene<V2> {
+	Scene::root(obj_import::convert(QUAD, false).unwrap())
+}
+
+#[test]
+fn welds_shared_corners_and_removes_bad_triangles() {
+	let mut scene = quad();
+	let welded = weld::weld(&mut scene, 0.01);
+
+	assert_eq!((welded.vertices_before, welded.vertices_after), (7, 4));
+	assert_eq!((welded.degenerate, welded.duplicate), (1, 1));
+
+	let model = &scene.model;
+
+	assert_eq!(model.frames[0].vertices.len(), 4);
+	assert_eq!(model.lod_levels[0].len(), 2);
+	assert_eq!((model.materials[0].vertex_count, model.materials[0].triangles[0].len), (4, 2));
+
+	convert::update_normals(&mut scene, Some(NormalMode::Smooth));
+	cemconv::validate::check(&scene).unwrap();
+}
+
+#[test]
+fn exact_welding_keeps_separate_positions() {
+	let mut scene = quad();
+	let welded = weld::weld(&mut scene, 0.0);
+
+	assert_eq!((welded.vertices_before, welded.vertices_after), (7, 5));
+	assert_eq!((welded.degenerate, welded.duplicate), (0, 1));
+	assert_eq!(scene.model.lod_levels[0].len(), 3);
+}
+
+#[test]
+fn welding_is_idempotent() {
+	let mut scene = quad();
+	weld::weld(&mut scene, 0.01);
+
+	let again = weld::weld(&mut scene, 0.01);
+
+	assert_eq!((again.vertices_before, again.vertices_after, again.degenerate, again.duplicate), (4, 4, 0, 0));
+}